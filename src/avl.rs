@@ -0,0 +1,208 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{id, TreeNode, TreeNodeRef};
+
+/// The payload of a node in one of the crate's balanced search trees, such as `IntervalTree`
+/// and `OrderStatisticTree`. Besides its key it keeps the height of its subtree and whatever
+/// else the tree needs to know about the subtree, all recomputed by `update`.
+pub(crate) trait AvlPayload: Sized + Copy {
+    type Key: Ord + Copy;
+
+    fn key(&self) -> Self::Key;
+    fn height(&self) -> usize;
+    /// Recomputes the height and other subtree information from the children.
+    fn update(&mut self, left: Option<&Self>, right: Option<&Self>);
+}
+
+/// Wraps a payload in a new node. Built directly rather than with `TreeNode::new_rc`, which
+/// would require the payload to implement `Display`.
+pub(crate) fn leaf<A: AvlPayload>(payload: A) -> TreeNodeRef<A> {
+    Rc::new(RefCell::new(TreeNode {
+        value: payload,
+        left: None,
+        right: None,
+        id: id::next_id(),
+    }))
+}
+
+/// The payload of the child, if any.
+pub(crate) fn payload<A: AvlPayload>(link: &Option<TreeNodeRef<A>>) -> Option<A> {
+    link.as_ref().map(|node_ref| node_ref.borrow().value)
+}
+
+fn height<A: AvlPayload>(link: &Option<TreeNodeRef<A>>) -> usize {
+    payload(link).map_or(0, |payload| payload.height())
+}
+
+fn balance_factor<A: AvlPayload>(node_ref: &TreeNodeRef<A>) -> isize {
+    let node = node_ref.borrow();
+    height(&node.left) as isize - height(&node.right) as isize
+}
+
+fn update<A: AvlPayload>(node_ref: &TreeNodeRef<A>) {
+    let mut node = node_ref.borrow_mut();
+    let left = payload(&node.left);
+    let right = payload(&node.right);
+    node.value.update(left.as_ref(), right.as_ref());
+}
+
+fn rotate_right<A: AvlPayload>(node_ref: TreeNodeRef<A>) -> TreeNodeRef<A> {
+    let pivot = node_ref
+        .borrow_mut()
+        .left
+        .take()
+        .expect("rotating right needs a left child");
+    node_ref.borrow_mut().left = pivot.borrow_mut().right.take();
+    update(&node_ref);
+    pivot.borrow_mut().right = Some(node_ref);
+    update(&pivot);
+    pivot
+}
+
+fn rotate_left<A: AvlPayload>(node_ref: TreeNodeRef<A>) -> TreeNodeRef<A> {
+    let pivot = node_ref
+        .borrow_mut()
+        .right
+        .take()
+        .expect("rotating left needs a right child");
+    node_ref.borrow_mut().right = pivot.borrow_mut().left.take();
+    update(&node_ref);
+    pivot.borrow_mut().left = Some(node_ref);
+    update(&pivot);
+    pivot
+}
+
+/// Updates a node whose children changed and rotates it back into balance, returning the node
+/// now at its place.
+fn rebalance<A: AvlPayload>(node_ref: TreeNodeRef<A>) -> TreeNodeRef<A> {
+    update(&node_ref);
+    let factor = balance_factor(&node_ref);
+    if factor > 1 {
+        let left = node_ref.borrow_mut().left.take().expect("left is higher");
+        let left = if balance_factor(&left) < 0 {
+            rotate_left(left)
+        } else {
+            left
+        };
+        node_ref.borrow_mut().left = Some(left);
+        rotate_right(node_ref)
+    } else if factor < -1 {
+        let right = node_ref.borrow_mut().right.take().expect("right is higher");
+        let right = if balance_factor(&right) > 0 {
+            rotate_right(right)
+        } else {
+            right
+        };
+        node_ref.borrow_mut().right = Some(right);
+        rotate_left(node_ref)
+    } else {
+        node_ref
+    }
+}
+
+/// Inserts `new` into the subtree, returning its new root. Equal keys go to the right.
+pub(crate) fn insert<A: AvlPayload>(
+    link: Option<TreeNodeRef<A>>,
+    new: TreeNodeRef<A>,
+) -> TreeNodeRef<A> {
+    let Some(node_ref) = link else {
+        return new;
+    };
+    let goes_left = new.borrow().value.key() < node_ref.borrow().value.key();
+    {
+        let mut node = node_ref.borrow_mut();
+        if goes_left {
+            let left = node.left.take();
+            node.left = Some(insert(left, new));
+        } else {
+            let right = node.right.take();
+            node.right = Some(insert(right, new));
+        }
+    }
+    rebalance(node_ref)
+}
+
+/// Removes one node with the key `target`, returning the new root of the subtree and the payload
+/// of the removed node.
+pub(crate) fn remove<A: AvlPayload>(
+    link: Option<TreeNodeRef<A>>,
+    target: A::Key,
+) -> (Option<TreeNodeRef<A>>, Option<A>) {
+    let Some(node_ref) = link else {
+        return (None, None);
+    };
+    let node_key = node_ref.borrow().value.key();
+    let removed = if target == node_key {
+        let (left, right) = {
+            let mut node = node_ref.borrow_mut();
+            (node.left.take(), node.right.take())
+        };
+        let removed = Some(node_ref.borrow().value);
+        return match (left, right) {
+            (None, only) | (only, None) => (only, removed),
+            (Some(left), Some(right)) => {
+                // The smallest node on the right takes the place of the removed one.
+                let (rest, successor) = remove_min(right);
+                {
+                    let mut node = successor.borrow_mut();
+                    node.left = Some(left);
+                    node.right = rest;
+                }
+                (Some(rebalance(successor)), removed)
+            }
+        };
+    } else {
+        let mut node = node_ref.borrow_mut();
+        if target < node_key {
+            let (left, removed) = remove(node.left.take(), target);
+            node.left = left;
+            removed
+        } else {
+            let (right, removed) = remove(node.right.take(), target);
+            node.right = right;
+            removed
+        }
+    };
+    (Some(rebalance(node_ref)), removed)
+}
+
+/// Detaches the leftmost node, returning the rest of the subtree and that node.
+fn remove_min<A: AvlPayload>(node_ref: TreeNodeRef<A>) -> (Option<TreeNodeRef<A>>, TreeNodeRef<A>) {
+    let left = node_ref.borrow_mut().left.take();
+    match left {
+        None => {
+            let right = node_ref.borrow_mut().right.take();
+            (right, node_ref)
+        }
+        Some(left) => {
+            let (rest, min) = remove_min(left);
+            node_ref.borrow_mut().left = rest;
+            (Some(rebalance(node_ref)), min)
+        }
+    }
+}
+
+/// Checks the order, balance and subtree information of every node, returning the height.
+#[cfg(test)]
+pub(crate) fn check<A: AvlPayload + PartialEq + std::fmt::Debug>(
+    link: &Option<TreeNodeRef<A>>,
+) -> usize {
+    let Some(node_ref) = link else {
+        return 0;
+    };
+    let node = node_ref.borrow();
+    let left = check(&node.left);
+    let right = check(&node.right);
+    assert!(left.abs_diff(right) <= 1);
+    let mut expected = node.value;
+    expected.update(payload(&node.left).as_ref(), payload(&node.right).as_ref());
+    assert_eq!(expected, node.value);
+    if let Some(child) = payload(&node.left) {
+        assert!(child.key() <= node.value.key());
+    }
+    if let Some(child) = payload(&node.right) {
+        assert!(child.key() >= node.value.key());
+    }
+    node.value.height()
+}
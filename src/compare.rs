@@ -0,0 +1,318 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNodeRef};
+
+/// Walks two trees side by side and checks every pair of nodes with `same`. Children are paired
+/// left with left, or left with right when `mirrored`. Returns false as soon as a pair differs or
+/// one side has a child the other lacks.
+fn walk_pairs<T: Sized + Copy>(
+    a: &TreeNodeRef<T>,
+    b: &TreeNodeRef<T>,
+    same: impl Fn(&TreeNodeRef<T>, &TreeNodeRef<T>) -> bool,
+    mirrored: bool,
+) -> bool {
+    let mut stack: Vec<(TreeNodeRef<T>, TreeNodeRef<T>)> = vec![(a.clone(), b.clone())];
+    while let Some((a, b)) = stack.pop() {
+        if !same(&a, &b) {
+            return false;
+        }
+        let (a, b) = (a.borrow(), b.borrow());
+        let (b_left, b_right) = if mirrored {
+            (&b.right, &b.left)
+        } else {
+            (&b.left, &b.right)
+        };
+        for (a_child, b_child) in [(&a.left, b_left), (&a.right, b_right)] {
+            match (a_child, b_child) {
+                (Some(a_child), Some(b_child)) => stack.push((a_child.clone(), b_child.clone())),
+                (None, None) => {}
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+/// Gives every distinct subtree shape a small number, so two subtrees are equal exactly when
+/// their numbers are. One interner can be shared across several trees to compare subtrees between
+/// them.
+struct SubtreeInterner<T> {
+    classes: HashMap<(T, Option<usize>, Option<usize>), usize>,
+    /// Whether the two children are put in a fixed order first, making the numbering blind to
+    /// child swaps.
+    unordered: bool,
+}
+
+impl<T: Sized + Copy + Debug + Display + Eq + Hash> SubtreeInterner<T> {
+    fn new(unordered: bool) -> Self {
+        SubtreeInterner {
+            classes: HashMap::new(),
+            unordered,
+        }
+    }
+
+    /// The class of every node of `tree`, in the order of `Tree::pre_order_with_parents`.
+    fn classify(&mut self, tree: &Tree<T>) -> Vec<(TreeNodeRef<T>, usize)> {
+        let order = tree.pre_order_with_parents();
+        let mut class_of: HashMap<Uuid, usize> = HashMap::with_capacity(order.len());
+        let mut classes: Vec<(TreeNodeRef<T>, usize)> = Vec::with_capacity(order.len());
+        for (node_ref, _) in order.into_iter().rev() {
+            let node = node_ref.borrow();
+            let class = |child: &Option<TreeNodeRef<T>>| {
+                child.as_ref().map(|child| class_of[&child.borrow().id])
+            };
+            let (mut left, mut right) = (class(&node.left), class(&node.right));
+            if self.unordered && left > right {
+                std::mem::swap(&mut left, &mut right);
+            }
+            let next = self.classes.len();
+            let class = *self
+                .classes
+                .entry((node.value, left, right))
+                .or_insert(next);
+            class_of.insert(node.id, class);
+            drop(node);
+            classes.push((node_ref, class));
+        }
+        classes.reverse();
+        classes
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display + PartialEq> Tree<T> {
+    /// Whether both trees have the same shape with the same values and the same ids in every
+    /// position. `==` compares values and shape only.
+    pub fn is_identical(&self, other: &Tree<T>) -> bool {
+        walk_pairs(
+            &self.root,
+            &other.root,
+            |a, b| {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.value == b.value && a.id == b.id
+            },
+            false,
+        )
+    }
+
+    /// Whether `other` is this tree reflected left to right: the same values, with every node's
+    /// left and right children swapped.
+    pub fn is_mirror_of(&self, other: &Tree<T>) -> bool {
+        walk_pairs(
+            &self.root,
+            &other.root,
+            |a, b| a.borrow().value == b.borrow().value,
+            true,
+        )
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display + Eq + Hash> Tree<T> {
+    /// Whether the trees hold the same values in the same shape up to swapping the children of
+    /// any number of nodes.
+    pub fn is_isomorphic_to(&self, other: &Tree<T>) -> bool {
+        let mut interner = SubtreeInterner::new(true);
+        let ours = interner.classify(self);
+        let theirs = interner.classify(other);
+        ours[0].1 == theirs[0].1
+    }
+
+    /// Whether some node of this tree is the root of a subtree equal to `other`, with the same
+    /// values in the same shape all the way down to the leaves.
+    pub fn contains_subtree(&self, other: &Tree<T>) -> bool {
+        self.find_subtrees(other).next().is_some()
+    }
+
+    /// The roots of every subtree of this tree equal to `other`, in pre-order.
+    pub fn find_subtrees(&self, other: &Tree<T>) -> impl Iterator<Item = TreeNodeRef<T>> {
+        let mut interner = SubtreeInterner::new(false);
+        let target = interner.classify(other)[0].1;
+        interner
+            .classify(self)
+            .into_iter()
+            .filter(move |(_, class)| *class == target)
+            .map(|(node_ref, _)| node_ref)
+    }
+
+    /// A hash of the tree's values and shape. Equal trees, in the `==` sense, hash equally.
+    pub fn structural_hash(&self) -> u64 {
+        self.subtree_hashes(false)[0].1
+    }
+
+    /// A hash that ignores the order of children, so isomorphic trees (see
+    /// `Tree::is_isomorphic_to`) hash equally. Useful for bucketing trees before comparing them.
+    pub fn canonical_hash(&self) -> u64 {
+        self.subtree_hashes(true)[0].1
+    }
+
+    /// Groups the subtrees of all trees in `forest` that are equal to each other, reporting only
+    /// groups of two or more. Each group lists the subtree roots in the order they were found.
+    /// Leaves count as subtrees too, so repeated values show up as duplicates.
+    pub fn duplicate_subtrees(forest: &[Tree<T>]) -> Vec<Vec<TreeNodeRef<T>>> {
+        let mut interner = SubtreeInterner::new(false);
+        let mut groups: Vec<Vec<TreeNodeRef<T>>> = Vec::new();
+        let mut group_of: HashMap<usize, usize> = HashMap::new();
+        for tree in forest {
+            for (node_ref, class) in interner.classify(tree) {
+                let next = groups.len();
+                let group = *group_of.entry(class).or_insert(next);
+                if group == next {
+                    groups.push(Vec::new());
+                }
+                groups[group].push(node_ref);
+            }
+        }
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
+    /// The hash of the subtree rooted at every node, in the order of
+    /// `Tree::pre_order_with_parents`.
+    fn subtree_hashes(&self, unordered: bool) -> Vec<(TreeNodeRef<T>, u64)> {
+        let order = self.pre_order_with_parents();
+        let mut hash_of: HashMap<Uuid, u64> = HashMap::with_capacity(order.len());
+        let mut hashes: Vec<(TreeNodeRef<T>, u64)> = Vec::with_capacity(order.len());
+        for (node_ref, _) in order.into_iter().rev() {
+            let node = node_ref.borrow();
+            let hash = |child: &Option<TreeNodeRef<T>>| {
+                child.as_ref().map(|child| hash_of[&child.borrow().id])
+            };
+            let (mut left, mut right) = (hash(&node.left), hash(&node.right));
+            if unordered && left > right {
+                std::mem::swap(&mut left, &mut right);
+            }
+            let mut hasher = DefaultHasher::new();
+            (node.value, left, right).hash(&mut hasher);
+            let hash = hasher.finish();
+            hash_of.insert(node.id, hash);
+            drop(node);
+            hashes.push((node_ref, hash));
+        }
+        hashes.reverse();
+        hashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \
+    //             4   5
+    //
+    fn test_tree() -> Tree<i32> {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    // Test tree, the test tree with the children of 1 swapped:
+    //                 1
+    //                / \
+    //               3   2
+    //                  / \
+    //                 4   5
+    //
+    fn swapped_tree() -> Tree<i32> {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        Tree::new(TreeNode::new_rc(1, Some(node3), Some(node2)))
+    }
+
+    #[test]
+    fn identical() {
+        let tree = test_tree();
+        let copy = tree.deep_clone();
+        let renewed = tree.deep_clone_with_new_ids();
+        assert!(tree.is_identical(&copy));
+        assert_eq!(tree, renewed);
+        assert!(!tree.is_identical(&renewed));
+    }
+
+    #[test]
+    fn mirror() {
+        let tree = test_tree();
+        let mirror = swapped_tree();
+        assert!(!tree.is_mirror_of(&mirror));
+
+        // Mirroring also swaps the children of 2.
+        let node2 = mirror.root.borrow().right.clone().unwrap();
+        let mut node2 = node2.borrow_mut();
+        let left = node2.left.take();
+        node2.left = node2.right.take();
+        node2.right = left;
+        drop(node2);
+        assert!(tree.is_mirror_of(&mirror));
+        assert!(mirror.is_mirror_of(&tree));
+    }
+
+    #[test]
+    fn isomorphic() {
+        let tree = test_tree();
+        let swapped = swapped_tree();
+        assert_ne!(tree, swapped);
+        assert!(tree.is_isomorphic_to(&swapped));
+        assert_eq!(tree.canonical_hash(), swapped.canonical_hash());
+        assert_ne!(tree.structural_hash(), swapped.structural_hash());
+        assert_eq!(
+            tree.structural_hash(),
+            tree.deep_clone_with_new_ids().structural_hash()
+        );
+
+        let other = Tree::new(TreeNode::new_rc(
+            1,
+            Some(TreeNode::new_rc(2, None, None)),
+            Some(TreeNode::new_rc(3, None, None)),
+        ));
+        assert!(!tree.is_isomorphic_to(&other));
+    }
+
+    #[test]
+    fn subtrees() {
+        let tree = test_tree();
+        let subtree = Tree::new(TreeNode::new_rc(
+            2,
+            Some(TreeNode::new_rc(4, None, None)),
+            Some(TreeNode::new_rc(5, None, None)),
+        ));
+        assert!(tree.contains_subtree(&subtree));
+        assert!(swapped_tree().contains_subtree(&subtree));
+
+        // 2 with only a left child is not a subtree, as subtrees run all the way to the leaves.
+        let partial = Tree::new(TreeNode::new_rc(
+            2,
+            Some(TreeNode::new_rc(4, None, None)),
+            None,
+        ));
+        assert!(!tree.contains_subtree(&partial));
+
+        let found: Vec<i32> = tree
+            .find_subtrees(&Tree::new(TreeNode::new_rc(5, None, None)))
+            .map(|node| node.borrow().value)
+            .collect();
+        assert_eq!(found, vec![5]);
+    }
+
+    #[test]
+    fn duplicate_subtrees() {
+        let forest = vec![test_tree(), swapped_tree()];
+        let groups: Vec<Vec<i32>> = Tree::duplicate_subtrees(&forest)
+            .iter()
+            .map(|group| group.iter().map(|node| node.borrow().value).collect())
+            .collect();
+        assert_eq!(groups, vec![vec![2, 2], vec![4, 4], vec![5, 5], vec![3, 3]]);
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::{Debug, Display};
+
+use uuid::Uuid;
+
+use crate::edit::Side;
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// A position in a tree that can be moved around and edited through, see `Tree::cursor`.
+///
+/// The cursor holds the tree mutably for as long as it lives and only borrows nodes for the
+/// duration of each call, so navigating and editing through it cannot run into conflicting
+/// `RefCell` borrows. Edits act on the current node directly, without looking it up by id, and
+/// keep an id index, tracked sizes and cached annotations correct.
+pub struct TreeCursor<'a, T: Sized + Copy> {
+    tree: &'a mut Tree<T>,
+    /// The ancestors of the current node, root first, each with the side taken below it.
+    path: Vec<(TreeNodeRef<T>, Side)>,
+    current: TreeNodeRef<T>,
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// A cursor starting at the root.
+    pub fn cursor(&mut self) -> TreeCursor<'_, T> {
+        let current = self.root.clone();
+        TreeCursor {
+            tree: self,
+            path: Vec::new(),
+            current,
+        }
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> TreeCursor<'_, T> {
+    /// The node under the cursor.
+    pub fn node(&self) -> TreeNodeRef<T> {
+        self.current.clone()
+    }
+
+    pub fn value(&self) -> T {
+        self.current.borrow().value
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.current.borrow().id
+    }
+
+    /// The number of edges between the root and the current node.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Whether the current node has a child on the given side.
+    pub fn has_child(&self, side: Side) -> bool {
+        self.current.borrow().child(side).is_some()
+    }
+
+    /// Moves to the child on the given side. Fails, staying put, if there is none.
+    pub fn move_to(&mut self, side: Side) -> Result<&mut Self, String> {
+        let child = self
+            .current
+            .borrow()
+            .child(side)
+            .clone()
+            .ok_or_else(|| format!("Attempted to move to a missing {} child", side))?;
+        let parent = std::mem::replace(&mut self.current, child);
+        self.path.push((parent, side));
+        Ok(self)
+    }
+
+    pub fn move_left(&mut self) -> Result<&mut Self, String> {
+        self.move_to(Side::Left)
+    }
+
+    pub fn move_right(&mut self) -> Result<&mut Self, String> {
+        self.move_to(Side::Right)
+    }
+
+    /// Moves to the parent. Fails, staying put, at the root.
+    pub fn move_parent(&mut self) -> Result<&mut Self, String> {
+        let (parent, _) = self
+            .path
+            .pop()
+            .ok_or_else(|| "Attempted to move above the root of the tree".to_string())?;
+        self.current = parent;
+        Ok(self)
+    }
+
+    pub fn move_root(&mut self) -> &mut Self {
+        self.path.clear();
+        self.current = self.tree.root.clone();
+        self
+    }
+
+    /// Replaces the value of the current node, returning the old one.
+    pub fn replace_value(&mut self, value: T) -> T {
+        std::mem::replace(&mut self.current.borrow_mut().value, value)
+    }
+
+    /// Adds a new leaf holding `value` on the given side of the current node, without moving.
+    /// Fails if there already is a child on that side.
+    pub fn insert_child(&mut self, side: Side, value: T) -> Result<&mut Self, String> {
+        self.tree.debug_validate();
+        if self.has_child(side) {
+            return Err(format!(
+                "Attempted to add a {} child to a node that already has one",
+                side
+            ));
+        }
+        let node = TreeNode::new_rc(value, None, None);
+        *self.current.borrow_mut().child_mut(side) = Some(node.clone());
+        let parent_id = self.id();
+        self.tree.index_subtree(&node, Some(parent_id));
+        self.tree.sizes_attached(&node, parent_id);
+        self.tree.invalidate_annotations();
+        Ok(self)
+    }
+
+    pub fn insert_left(&mut self, value: T) -> Result<&mut Self, String> {
+        self.insert_child(Side::Left, value)
+    }
+
+    pub fn insert_right(&mut self, value: T) -> Result<&mut Self, String> {
+        self.insert_child(Side::Right, value)
+    }
+
+    /// Cuts the current node and its descendants out of the tree and returns them, moving the
+    /// cursor to the parent. Fails at the root.
+    pub fn delete_subtree(&mut self) -> Result<Tree<T>, String> {
+        self.tree.debug_validate();
+        let (parent, side) = self
+            .path
+            .pop()
+            .ok_or_else(|| "Attempted to delete the root of the tree".to_string())?;
+        let removed = parent
+            .borrow_mut()
+            .child_mut(side)
+            .take()
+            .expect("cursor path matches the tree");
+        let parent_id = parent.borrow().id;
+        self.current = parent;
+        self.tree.unindex_subtree(&removed);
+        self.tree.sizes_detached(&removed, parent_id);
+        self.tree.invalidate_annotations();
+        Ok(Tree::new(removed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigate_and_edit() {
+        let mut tree = Tree::new_indexed(TreeNode::new_rc(1, None, None));
+        let mut cursor = tree.cursor();
+        cursor.insert_left(2).unwrap().insert_right(3).unwrap();
+        cursor.move_left().unwrap().insert_left(4).unwrap();
+        cursor.insert_right(5).unwrap();
+        assert_eq!(cursor.value(), 2);
+        assert_eq!(cursor.depth(), 1);
+
+        // Test tree:
+        //                 1
+        //                / \
+        //               2   3
+        //              / \
+        //             4   5
+        //
+        cursor.move_right().unwrap();
+        assert_eq!(cursor.replace_value(50), 5);
+        assert_eq!(
+            cursor.move_right().err(),
+            Some("Attempted to move to a missing right child".to_string())
+        );
+        assert_eq!(cursor.value(), 50);
+
+        cursor.move_parent().unwrap().move_parent().unwrap();
+        assert!(cursor.is_root());
+        assert!(cursor.move_parent().is_err());
+        assert_eq!(
+            cursor.delete_subtree().err(),
+            Some("Attempted to delete the root of the tree".to_string())
+        );
+
+        cursor.move_left().unwrap().move_left().unwrap();
+        let removed = cursor.delete_subtree().unwrap();
+        assert_eq!(removed.typst_string(), "[4]");
+        assert_eq!(cursor.value(), 2);
+        assert!(!cursor.has_child(Side::Left));
+        assert_eq!(cursor.move_root().value(), 1);
+
+        assert_eq!(tree.typst_string(), "([1], ([2], [50]), [3])");
+        assert!(tree.validate().is_valid());
+    }
+
+    #[test]
+    fn insert_below_current_node() {
+        // Two nodes with the same id, an id lookup would find the left one first.
+        let left = TreeNode::new_rc(2, None, None);
+        let right = TreeNode::new_rc(3, None, None);
+        right.borrow_mut().id = left.borrow().get_id();
+        let mut tree = Tree::new(TreeNode::new_rc(1, Some(left), Some(right)));
+        tree.track_sizes();
+
+        let mut cursor = tree.cursor();
+        cursor.move_right().unwrap().insert_left(4).unwrap();
+        assert_eq!(
+            cursor.insert_left(5).err(),
+            Some("Attempted to add a left child to a node that already has one".to_string())
+        );
+        assert_eq!(tree.typst_string(), "([1], [2], ([3], [4], ))");
+        assert_eq!(tree.size(), 4);
+    }
+}
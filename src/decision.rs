@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::Write;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef, TYPST_IMPORT};
+
+/// What a decision tree predicts at a leaf.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Class(usize),
+    Value(f64),
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Class(class) => write!(f, "class {}", class),
+            Outcome::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Identifies a node by its address rather than its id, so that nodes sharing an id stay apart.
+type NodePtr = *const TreeNode<DecisionNode>;
+
+/// The payload of a decision tree node. A split sends samples whose `feature` is at most
+/// `threshold` to the left child and all others to the right child.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecisionNode {
+    Split { feature: usize, threshold: f64 },
+    Leaf(Outcome),
+}
+
+/// Splits show the feature they test as `x2`, leaves their outcome.
+impl Display for DecisionNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecisionNode::Split { feature, .. } => write!(f, "x{}", feature),
+            DecisionNode::Leaf(outcome) => write!(f, "{}", outcome),
+        }
+    }
+}
+
+impl Tree<DecisionNode> {
+    /// The outcome for the sample with the given feature values. Fails if a split tests a
+    /// feature the sample does not have or lacks the child the sample goes to.
+    pub fn predict(&self, features: &[f64]) -> Result<Outcome, String> {
+        self.follow(features).map(|(_, outcome)| outcome)
+    }
+
+    /// `Tree::predict` for every sample, failing on the first sample that fails.
+    pub fn predict_batch<S: AsRef<[f64]>>(&self, samples: &[S]) -> Result<Vec<Outcome>, String> {
+        samples
+            .iter()
+            .map(|features| self.predict(features.as_ref()))
+            .collect()
+    }
+
+    /// The ids of the nodes the sample passes on its way to a leaf, from the root to the leaf.
+    pub fn decision_path(&self, features: &[f64]) -> Result<Vec<Uuid>, String> {
+        self.follow(features).map(|(path, _)| path)
+    }
+
+    fn follow(&self, features: &[f64]) -> Result<(Vec<Uuid>, Outcome), String> {
+        let mut path: Vec<Uuid> = Vec::new();
+        let mut current = self.root.clone();
+        loop {
+            let next = {
+                let node = current.borrow();
+                path.push(node.id);
+                let (feature, threshold) = match node.value {
+                    DecisionNode::Leaf(outcome) => return Ok((path, outcome)),
+                    DecisionNode::Split { feature, threshold } => (feature, threshold),
+                };
+                let value = features.get(feature).ok_or_else(|| {
+                    format!(
+                        "Attempted to predict with {} features on a tree that splits on feature {}",
+                        features.len(),
+                        feature
+                    )
+                })?;
+                // NaN fails the comparison and goes right.
+                let child = if *value <= threshold {
+                    &node.left
+                } else {
+                    &node.right
+                };
+                child.clone().ok_or_else(|| {
+                    "Attempted to predict through a split that is missing a child".to_string()
+                })?
+            };
+            current = next;
+        }
+    }
+
+    /// The label of the edge from each split to its children, by child.
+    fn edge_labels(&self) -> HashMap<NodePtr, String> {
+        let mut labels: HashMap<NodePtr, String> = HashMap::new();
+        for (node_ref, _) in self.pre_order_with_parents() {
+            let node = node_ref.borrow();
+            if let DecisionNode::Split { threshold, .. } = node.value {
+                let mut label = |child: &Option<TreeNodeRef<DecisionNode>>, op: &str| {
+                    if let Some(child) = child {
+                        labels.insert(child.as_ptr(), format!("{} {}", op, threshold));
+                    }
+                };
+                label(&node.left, "≤");
+                label(&node.right, ">");
+            }
+        }
+        labels
+    }
+
+    /// Like `Tree::typst_string`, but every node is a dictionary holding its `label` and the
+    /// `edge` label of the edge leading to it, as drawn by `Tree::save_decision_typst`.
+    pub fn decision_typst_string(&self) -> String {
+        let edges = self.edge_labels();
+        self.root.borrow().format_typst_with(&|node| {
+            let edge = edges
+                .get(&(node as NodePtr))
+                .map_or("none".to_string(), |edge| format!("[{}]", edge));
+            format!("(label: [{}], edge: {})", node.value, edge)
+        })
+    }
+
+    /// Like `Tree::save_typst`, with the edges labelled `≤ threshold` and `> threshold`.
+    pub fn save_decision_typst(&self, file: &str) -> std::io::Result<()> {
+        let mut f = File::create(file)?;
+        let mut out_string = String::new();
+        out_string.push_str(&format!(
+            "\n#let data = (\n{}\n)\n",
+            self.decision_typst_string()
+        ));
+        out_string.push_str(TYPST_IMPORT);
+        out_string.push('#');
+        out_string.push_str(&decision_canvas("data"));
+        f.write_all(out_string.as_bytes())
+    }
+
+    /// The tree in Graphviz DOT format, with the edges labelled `≤ threshold` and
+    /// `> threshold`. Nodes are named by their position in pre-order.
+    pub fn to_dot(&self) -> String {
+        let edges = self.edge_labels();
+        let order = self.pre_order_with_parents();
+        let position: HashMap<NodePtr, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(position, (node_ref, _))| (node_ref.as_ptr() as NodePtr, position))
+            .collect();
+        let mut out = String::from("digraph {\n    node [shape=box];\n");
+        for (position, (node_ref, _)) in order.iter().enumerate() {
+            out.push_str(&format!(
+                "    n{} [label=\"{}\"];\n",
+                position,
+                node_ref.borrow().value
+            ));
+        }
+        for (node_ref, parent) in &order {
+            if let Some(parent) = parent {
+                let node = node_ref.as_ptr() as NodePtr;
+                out.push_str(&format!(
+                    "    n{} -> n{} [label=\"{}\"];\n",
+                    position[&(parent.as_ptr() as NodePtr)],
+                    position[&node],
+                    edges.get(&node).map_or("", String::as_str)
+                ));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Like the canvas of `Tree::save_typst`, for the node dictionaries of
+/// `Tree::decision_typst_string`: the edge label is drawn halfway along the edge.
+fn decision_canvas(data: &str) -> String {
+    r#"canvas(length: 1cm, {
+  import draw: *
+
+  set-style(content: (padding: .2),
+    fill: gray.lighten(70%),
+    stroke: gray.lighten(70%))
+
+  tree.tree(DATA, spread: 2.5, grow: 1.5, draw-node: (node, _) => {
+    circle((), radius: .45, stroke: none)
+    content((), node.content.label)
+  }, draw-edge: (from, to, node) => {
+    line((a: from, number: .6, abs: true, b: to),
+         (a: to, number: .6, abs: true, b: from), mark: (end: ">"))
+    content((a: from, number: .5, b: to), text(size: 8pt, node.content.edge))
+  }, name: "tree")
+})
+"#
+    .replace("DATA", data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    // Test tree:
+    //                x0 ≤ 2.5
+    //               /        \
+    //          class 0      x1 ≤ 1
+    //                      /      \
+    //                 class 1    class 2
+    //
+    fn test_tree() -> Tree<DecisionNode> {
+        let leaf = |class| TreeNode::new_rc(DecisionNode::Leaf(Outcome::Class(class)), None, None);
+        let split = |feature, threshold, left, right| {
+            TreeNode::new_rc(
+                DecisionNode::Split { feature, threshold },
+                Some(left),
+                Some(right),
+            )
+        };
+        let inner = split(1, 1.0, leaf(1), leaf(2));
+        Tree::new(split(0, 2.5, leaf(0), inner))
+    }
+
+    #[test]
+    fn predict() {
+        let tree = test_tree();
+        assert_eq!(tree.predict(&[1.0, 5.0]), Ok(Outcome::Class(0)));
+        assert_eq!(tree.predict(&[2.5, 5.0]), Ok(Outcome::Class(0)));
+        assert_eq!(tree.predict(&[3.0, 1.0]), Ok(Outcome::Class(1)));
+        assert_eq!(tree.predict(&[3.0, f64::NAN]), Ok(Outcome::Class(2)));
+        assert_eq!(
+            tree.predict(&[3.0]),
+            Err(
+                "Attempted to predict with 1 features on a tree that splits on feature 1"
+                    .to_string()
+            )
+        );
+        // Samples that stop early don't need the later features.
+        assert_eq!(tree.predict(&[0.0]), Ok(Outcome::Class(0)));
+
+        let samples = vec![vec![0.0, 0.0], vec![9.0, 0.0], vec![9.0, 9.0]];
+        assert_eq!(
+            tree.predict_batch(&samples),
+            Ok(vec![
+                Outcome::Class(0),
+                Outcome::Class(1),
+                Outcome::Class(2)
+            ])
+        );
+        assert!(tree.predict_batch(&[[9.0]]).is_err());
+
+        let regression = Tree::new(TreeNode::new_rc(
+            DecisionNode::Leaf(Outcome::Value(0.75)),
+            None,
+            None,
+        ));
+        assert_eq!(regression.predict(&[]), Ok(Outcome::Value(0.75)));
+
+        let broken = Tree::new(TreeNode::new_rc(
+            DecisionNode::Split {
+                feature: 0,
+                threshold: 0.0,
+            },
+            None,
+            None,
+        ));
+        assert_eq!(
+            broken.predict(&[1.0]),
+            Err("Attempted to predict through a split that is missing a child".to_string())
+        );
+    }
+
+    #[test]
+    fn decision_path() {
+        let tree = test_tree();
+        let root = tree.root.clone();
+        let inner = root.borrow().right.clone().unwrap();
+        let leaf = inner.borrow().right.clone().unwrap();
+        let path = tree.decision_path(&[3.0, 2.0]).unwrap();
+        assert_eq!(
+            path,
+            vec![
+                root.borrow().get_id(),
+                inner.borrow().get_id(),
+                leaf.borrow().get_id()
+            ]
+        );
+        assert_eq!(tree.decision_path(&[0.0]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn export() {
+        let tree = test_tree();
+        assert_eq!(
+            tree.typst_string(),
+            "([x0], [class 0], ([x1], [class 1], [class 2]))"
+        );
+        assert_eq!(
+            tree.decision_typst_string(),
+            "((label: [x0], edge: none), (label: [class 0], edge: [≤ 2.5]), \
+             ((label: [x1], edge: [> 2.5]), (label: [class 1], edge: [≤ 1]), \
+             (label: [class 2], edge: [> 1])))"
+        );
+        assert_eq!(
+            tree.to_dot(),
+            "digraph {\n    node [shape=box];\n    n0 [label=\"x0\"];\n    \
+             n1 [label=\"class 0\"];\n    n2 [label=\"x1\"];\n    n3 [label=\"class 1\"];\n    \
+             n4 [label=\"class 2\"];\n    n0 -> n1 [label=\"≤ 2.5\"];\n    \
+             n0 -> n2 [label=\"> 2.5\"];\n    n2 -> n3 [label=\"≤ 1\"];\n    \
+             n2 -> n4 [label=\"> 1\"];\n}\n"
+        );
+
+        // Nodes with the same id, e.g. from a copy, are still told apart.
+        let id = tree.root.borrow().get_id();
+        for (node_ref, _) in tree.pre_order_with_parents() {
+            node_ref.borrow_mut().id = id;
+        }
+        assert!(tree.to_dot().contains("n2 -> n4 [label=\"> 1\"]"));
+        assert!(tree
+            .decision_typst_string()
+            .contains("(label: [class 2], edge: [> 1])"));
+
+        tree.save_decision_typst("decision_test.typ").unwrap();
+        let saved = std::fs::read_to_string("decision_test.typ").unwrap();
+        std::fs::remove_file("decision_test.typ").unwrap();
+        assert!(saved.contains("#let data = (\n((label: [x0], edge: none), "));
+        assert!(saved.contains("node.content.edge"));
+    }
+}
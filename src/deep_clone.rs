@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::edit::Side;
+use crate::id;
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+impl<T: Sized + Copy + Display> TreeNode<T> {
+    /// Copies this node and every node below it into new allocations, so the copy shares nothing
+    /// with the original. Every node of the copy keeps the id of the node it was copied from.
+    ///
+    /// Compare with `Clone`, which copies only this node and shares its children with the
+    /// original.
+    pub fn deep_clone(&self) -> TreeNode<T> {
+        self.copy_structure(|node| node.id)
+    }
+
+    /// Like `TreeNode::deep_clone`, but every node of the copy gets a new id from the current
+    /// `IdStrategy`.
+    pub fn deep_clone_with_new_ids(&self) -> TreeNode<T> {
+        self.copy_structure(|_| id::next_id())
+    }
+
+    fn copy_structure(&self, mut new_id: impl FnMut(&TreeNode<T>) -> Uuid) -> TreeNode<T> {
+        let root_copy = Rc::new(RefCell::new(TreeNode::new_with_id(
+            self.value,
+            None,
+            None,
+            new_id(self),
+        )));
+
+        // Each entry is a child still to be copied, with the copied parent it belongs under.
+        let mut stack: Vec<(TreeNodeRef<T>, TreeNodeRef<T>, Side)> = Vec::new();
+        for side in [Side::Right, Side::Left] {
+            if let Some(child) = self.child(side) {
+                stack.push((child.clone(), root_copy.clone(), side));
+            }
+        }
+        while let Some((original, parent_copy, side)) = stack.pop() {
+            let node = original.borrow();
+            let node_copy = Rc::new(RefCell::new(TreeNode::new_with_id(
+                node.value,
+                None,
+                None,
+                new_id(&node),
+            )));
+            for child_side in [Side::Right, Side::Left] {
+                if let Some(child) = node.child(child_side) {
+                    stack.push((child.clone(), node_copy.clone(), child_side));
+                }
+            }
+            *parent_copy.borrow_mut().child_mut(side) = Some(node_copy);
+        }
+
+        Rc::try_unwrap(root_copy)
+            .ok()
+            .expect("no other references to the copied root remain")
+            .into_inner()
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Copies the whole tree into new nodes, so changes to one tree never show up in the other.
+    /// Node ids are kept, so ids taken from this tree can be used to find the matching node in
+    /// the copy. Cached annotations are carried over, and an id index and tracked sizes are
+    /// rebuilt for the copy.
+    ///
+    /// `Tree::clone` on the other hand is a shallow copy: it clones the handle to the root, so
+    /// both trees keep pointing at the same nodes.
+    pub fn deep_clone(&self) -> Tree<T> {
+        let mut copy = Tree::new(Rc::new(RefCell::new(self.root.borrow().deep_clone())));
+        *copy.annotations.borrow_mut() = self.annotations.borrow().clone();
+        if self.has_index() {
+            copy.build_index();
+        }
+        if self.tracks_sizes() {
+            copy.track_sizes();
+        }
+        copy
+    }
+
+    /// Like `Tree::deep_clone`, but every node of the copy gets a new id from the current
+    /// `IdStrategy`, so the copy can be grafted into or compared against the original without
+    /// any id clashes.
+    pub fn deep_clone_with_new_ids(&self) -> Tree<T> {
+        let mut copy = Tree::new(Rc::new(RefCell::new(
+            self.root.borrow().deep_clone_with_new_ids(),
+        )));
+        if self.has_index() {
+            copy.build_index();
+        }
+        if self.tracks_sizes() {
+            copy.track_sizes();
+        }
+        copy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \
+    //             4   5
+    //
+    fn test_tree() -> Tree<i32> {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    #[test]
+    fn shallow_and_deep_clone() {
+        let tree = test_tree();
+        let shallow = tree.clone();
+        let deep = tree.deep_clone();
+        assert!(Rc::ptr_eq(&tree.root, &shallow.root));
+        assert!(!Rc::ptr_eq(&tree.root, &deep.root));
+        assert_eq!(tree, deep);
+
+        let id2 = tree.root.borrow().left.as_ref().unwrap().borrow().get_id();
+        deep.get_by_id(id2).unwrap().borrow_mut().value = 20;
+        assert_eq!(deep.typst_string(), "([1], ([20], [4], [5]), [3])");
+        assert_eq!(tree.typst_string(), "([1], ([2], [4], [5]), [3])");
+
+        shallow.get_by_id(id2).unwrap().borrow_mut().value = 200;
+        assert_eq!(tree.typst_string(), "([1], ([200], [4], [5]), [3])");
+    }
+
+    #[test]
+    fn deep_clone_ids() {
+        let mut tree = test_tree();
+        tree.build_index();
+        let ids: Vec<_> = tree
+            .pre_order_with_parents()
+            .iter()
+            .map(|(node, _)| node.borrow().get_id())
+            .collect();
+
+        let kept = tree.deep_clone();
+        assert!(kept.has_index());
+        for id in &ids {
+            let original = tree.get_by_id(*id).unwrap();
+            let copy = kept.get_by_id(*id).unwrap();
+            assert!(!Rc::ptr_eq(&original, &copy));
+            assert_eq!(original.borrow().value, copy.borrow().value);
+        }
+
+        let renewed = tree.deep_clone_with_new_ids();
+        assert_eq!(renewed.typst_string(), tree.typst_string());
+        assert!(ids.iter().all(|id| renewed.get_by_id(*id).is_none()));
+    }
+}
@@ -0,0 +1,702 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::edit::Side;
+use crate::id;
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// Identifies the node an `Edit` applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NodeKey {
+    /// The node with this id.
+    Id(Uuid),
+    /// The node reached by taking these sides on the way down from the root, the root itself when
+    /// empty.
+    Position(Vec<Side>),
+}
+
+/// The child slot on `side` of the node `parent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slot {
+    pub parent: NodeKey,
+    pub side: Side,
+}
+
+/// A single change produced by `Tree::diff` or `Tree::diff_by_position`, applied with
+/// `Tree::apply_patch`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit<T> {
+    /// Adds a new node holding `value` in the slot `at`, or as the new root when `at` is `None`.
+    Insert {
+        key: NodeKey,
+        at: Option<Slot>,
+        value: T,
+    },
+    /// Cuts a node out of the tree, together with any of its descendants that are not moved
+    /// elsewhere by the same patch.
+    Delete { key: NodeKey },
+    /// Changes the value of a node.
+    Relabel { key: NodeKey, from: T, to: T },
+    /// Moves a node, with its descendants, to the slot `to`, or makes it the root when `to` is
+    /// `None`.
+    Move { key: NodeKey, to: Option<Slot> },
+}
+
+impl<T> Edit<T> {
+    /// The node the edit applies to.
+    pub fn key(&self) -> &NodeKey {
+        match self {
+            Edit::Insert { key, .. }
+            | Edit::Delete { key }
+            | Edit::Relabel { key, .. }
+            | Edit::Move { key, .. } => key,
+        }
+    }
+}
+
+/// A node of each tree at the same position, either of which may be missing, and that position.
+type PairedNodes<T> = (Option<TreeNodeRef<T>>, Option<TreeNodeRef<T>>, Vec<Side>);
+
+type NodePtr<T> = *const RefCell<TreeNode<T>>;
+
+/// How a node that is resolved by `Tree::apply_patch` should change.
+enum Action<T: Sized + Copy> {
+    Place(TreeNodeRef<T>, Option<(TreeNodeRef<T>, Side)>),
+    Delete(TreeNodeRef<T>),
+    Relabel(TreeNodeRef<T>, T),
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// The value, parent id and side of every node, in pre-order.
+    fn placements(&self) -> Vec<(Uuid, T, Option<Slot>)> {
+        self.pre_order_with_parents()
+            .into_iter()
+            .map(|(node_ref, parent)| {
+                let node = node_ref.borrow();
+                let slot = parent.map(|parent| {
+                    let parent = parent.borrow();
+                    let side = match &parent.left {
+                        Some(left) if Rc::ptr_eq(left, &node_ref) => Side::Left,
+                        _ => Side::Right,
+                    };
+                    Slot {
+                        parent: NodeKey::Id(parent.id),
+                        side,
+                    }
+                });
+                (node.id, node.value, slot)
+            })
+            .collect()
+    }
+
+    /// The node at the end of `path`, starting from the root.
+    fn node_at(&self, path: &[Side]) -> Option<TreeNodeRef<T>> {
+        let mut current = self.root.clone();
+        for side in path {
+            let next = current.borrow().child(*side).clone()?;
+            current = next;
+        }
+        Some(current)
+    }
+
+    /// The node for `key` in this tree, if any.
+    fn resolve(&self, key: &NodeKey) -> Option<TreeNodeRef<T>> {
+        match key {
+            NodeKey::Id(id) => self.get_by_id(*id),
+            NodeKey::Position(path) => self.node_at(path),
+        }
+    }
+
+    /// The ids of the nodes of this tree that the edits refer to. On the tree a diff was taken
+    /// from this finds the deleted, moved and relabelled nodes, on the tree it was taken against
+    /// the inserted, moved and relabelled ones.
+    pub fn touched_by(&self, edits: &[Edit<T>]) -> Vec<Uuid> {
+        edits
+            .iter()
+            .filter_map(|edit| self.resolve(edit.key()))
+            .map(|node_ref| node_ref.borrow().id)
+            .collect()
+    }
+
+    /// Like `Tree::typst_string`, with the nodes whose ids are in `highlighted` drawn in red.
+    pub fn typst_string_highlighted(&self, highlighted: &[Uuid]) -> String {
+        self.root.borrow().format_typst_with(&|node| {
+            if highlighted.contains(&node.id) {
+                format!("[#text(fill: red)[{}]]", node.value)
+            } else {
+                format!("[{}]", node.value)
+            }
+        })
+    }
+
+    /// Like `Tree::terminal_string`, with the nodes whose ids are in `highlighted` marked with
+    /// asterisks.
+    pub fn terminal_string_highlighted(&self, width: usize, highlighted: &[Uuid]) -> String {
+        self.terminal_string_with(width, |node| {
+            if highlighted.contains(&node.id) {
+                format!("*{}*", node.value)
+            } else {
+                node.value.to_string()
+            }
+        })
+    }
+
+    /// Applies edits produced by `Tree::diff` or `Tree::diff_by_position`.
+    ///
+    /// All keys are looked up before anything changes, so an edit that refers to a missing node
+    /// leaves the tree untouched. Position keys refer to positions in this tree as it was before
+    /// the patch, except for the parents of inserted nodes, which may also be nodes inserted
+    /// earlier by the same patch. A patch that places a node into a slot that is still occupied,
+    /// places a node twice or below itself, or removes the root without replacing it, is rejected
+    /// before anything changes as well.
+    pub fn apply_patch(&mut self, edits: &[Edit<T>]) -> Result<(), String> {
+        self.debug_validate();
+
+        let mut inserted: HashMap<NodeKey, TreeNodeRef<T>> = HashMap::new();
+        for edit in edits {
+            if let Edit::Insert { key, value, .. } = edit {
+                let id = match key {
+                    NodeKey::Id(id) if self.get_by_id(*id).is_some() => {
+                        return Err("Attempted to insert a node whose id is already in the tree"
+                            .to_string());
+                    }
+                    NodeKey::Id(id) => *id,
+                    NodeKey::Position(_) => id::next_id(),
+                };
+                let node = TreeNode::new_with_id(*value, None, None, id);
+                if inserted
+                    .insert(key.clone(), Rc::new(RefCell::new(node)))
+                    .is_some()
+                {
+                    return Err("Attempted to insert two nodes with the same key".to_string());
+                }
+            }
+        }
+
+        let find = |key: &NodeKey| -> Result<TreeNodeRef<T>, String> {
+            inserted
+                .get(key)
+                .cloned()
+                .or_else(|| self.resolve(key))
+                .ok_or_else(|| "Attempted to patch a node that is not in the tree".to_string())
+        };
+        let find_slot = |slot: &Option<Slot>| -> Result<Option<(TreeNodeRef<T>, Side)>, String> {
+            slot.as_ref()
+                .map(|slot| find(&slot.parent).map(|parent| (parent, slot.side)))
+                .transpose()
+        };
+        let mut actions: Vec<Action<T>> = Vec::with_capacity(edits.len());
+        for edit in edits {
+            actions.push(match edit {
+                Edit::Insert { key, at, .. } => Action::Place(find(key)?, find_slot(at)?),
+                Edit::Move { key, to } => Action::Place(find(key)?, find_slot(to)?),
+                Edit::Delete { key } => Action::Delete(find(key)?),
+                Edit::Relabel { key, to, .. } => Action::Relabel(find(key)?, *to),
+            });
+        }
+
+        // Every moved or deleted node is cut loose from where it is now, freeing its slot.
+        let cut: HashSet<NodePtr<T>> = actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Place(node_ref, _) | Action::Delete(node_ref) => Some(Rc::as_ptr(node_ref)),
+                Action::Relabel(..) => None,
+            })
+            .collect();
+        let parents: HashMap<NodePtr<T>, TreeNodeRef<T>> = self
+            .pre_order_with_parents()
+            .into_iter()
+            .filter_map(|(node_ref, parent)| parent.map(|parent| (Rc::as_ptr(&node_ref), parent)))
+            .collect();
+
+        // Where each placed node ends up, `None` for the root.
+        let mut placed: HashMap<NodePtr<T>, Option<TreeNodeRef<T>>> = HashMap::new();
+        for action in &actions {
+            if let Action::Place(node_ref, slot) = action {
+                let parent = slot.as_ref().map(|(parent, _)| parent.clone());
+                if placed.insert(Rc::as_ptr(node_ref), parent).is_some() {
+                    return Err("Attempted to patch the same node into two places".to_string());
+                }
+            }
+        }
+
+        // Check that every slot is free by the time a node is placed into it.
+        let mut filled: HashSet<(NodePtr<T>, Side)> = HashSet::new();
+        let mut root_replaced = false;
+        for action in &actions {
+            match action {
+                Action::Place(_, Some((parent, side))) => {
+                    let kept = parent
+                        .borrow()
+                        .child(*side)
+                        .as_ref()
+                        .is_some_and(|child| !cut.contains(&Rc::as_ptr(child)));
+                    if kept || !filled.insert((Rc::as_ptr(parent), *side)) {
+                        return Err(format!(
+                            "Attempted to patch a node into an occupied {} slot",
+                            side
+                        ));
+                    }
+                }
+                Action::Place(_, None) => root_replaced = true,
+                Action::Relabel(..) | Action::Delete(_) => {}
+            }
+        }
+        if cut.contains(&Rc::as_ptr(&self.root)) && !root_replaced {
+            return Err("Attempted to remove the root without replacing it".to_string());
+        }
+
+        // Walk up from every new parent as the tree will be after the patch. Reaching the placed
+        // node, or any node, a second time means the patch would create a cycle.
+        for (&node, parent) in &placed {
+            let mut visited: HashSet<NodePtr<T>> = HashSet::from([node]);
+            let mut current = parent.clone();
+            while let Some(ancestor) = current {
+                let ancestor = Rc::as_ptr(&ancestor);
+                if !visited.insert(ancestor) {
+                    return Err("Attempted to patch a node below itself".to_string());
+                }
+                current = match placed.get(&ancestor) {
+                    Some(parent) => parent.clone(),
+                    None if cut.contains(&ancestor) => None,
+                    None => parents.get(&ancestor).cloned(),
+                };
+            }
+        }
+
+        for node in &cut {
+            if let Some(parent) = parents.get(node) {
+                let mut parent = parent.borrow_mut();
+                let side = match &parent.left {
+                    Some(left) if Rc::as_ptr(left) == *node => Side::Left,
+                    _ => Side::Right,
+                };
+                parent.child_mut(side).take();
+            }
+        }
+
+        for action in actions {
+            match action {
+                Action::Place(node_ref, Some((parent, side))) => {
+                    *parent.borrow_mut().child_mut(side) = Some(node_ref);
+                }
+                Action::Place(node_ref, None) => self.root = node_ref,
+                Action::Relabel(node_ref, value) => node_ref.borrow_mut().value = value,
+                Action::Delete(_) => {}
+            }
+        }
+
+        if self.has_index() {
+            self.build_index();
+        }
+        self.refresh_sizes();
+        self.invalidate_annotations();
+        Ok(())
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display + PartialEq> Tree<T> {
+    /// The edits that turn this tree into `other`, matching nodes by id. Suited to trees that
+    /// were derived from each other, e.g. with `Tree::deep_clone`, so that nodes kept their ids.
+    ///
+    /// Deletions come first, followed by the changes to `other`'s nodes in pre-order, so parents
+    /// are always inserted before their children.
+    pub fn diff(&self, other: &Tree<T>) -> Vec<Edit<T>> {
+        let theirs = other.placements();
+        let their_ids: HashSet<Uuid> = theirs.iter().map(|(id, _, _)| *id).collect();
+
+        let mut edits: Vec<Edit<T>> = Vec::new();
+        let mut ours: HashMap<Uuid, (T, Option<Slot>)> = HashMap::new();
+        for (id, value, slot) in self.placements() {
+            if !their_ids.contains(&id) {
+                edits.push(Edit::Delete {
+                    key: NodeKey::Id(id),
+                });
+            }
+            ours.insert(id, (value, slot));
+        }
+
+        for (id, value, slot) in theirs {
+            let key = NodeKey::Id(id);
+            match ours.get(&id) {
+                None => edits.push(Edit::Insert {
+                    key,
+                    at: slot,
+                    value,
+                }),
+                Some((our_value, our_slot)) => {
+                    if *our_slot != slot {
+                        edits.push(Edit::Move {
+                            key: key.clone(),
+                            to: slot,
+                        });
+                    }
+                    if *our_value != value {
+                        edits.push(Edit::Relabel {
+                            key,
+                            from: *our_value,
+                            to: value,
+                        });
+                    }
+                }
+            }
+        }
+        edits
+    }
+
+    /// The edits that turn this tree into `other`, matching nodes by their position below the
+    /// root and ignoring ids. Suited to trees built independently of each other. There are no
+    /// moves in a positional diff: a subtree that changed places is deleted and inserted again.
+    pub fn diff_by_position(&self, other: &Tree<T>) -> Vec<Edit<T>> {
+        let mut edits: Vec<Edit<T>> = Vec::new();
+        let mut stack: Vec<PairedNodes<T>> = vec![(
+            Some(self.root.clone()),
+            Some(other.root.clone()),
+            Vec::new(),
+        )];
+        while let Some((ours, theirs, path)) = stack.pop() {
+            let key = NodeKey::Position(path.clone());
+            let child = |node: &Option<TreeNodeRef<T>>, side: Side| {
+                node.as_ref()
+                    .and_then(|node| node.borrow().child(side).clone())
+            };
+            match (&ours, &theirs) {
+                (Some(our_node), Some(their_node)) => {
+                    let (from, to) = (our_node.borrow().value, their_node.borrow().value);
+                    if from != to {
+                        edits.push(Edit::Relabel { key, from, to });
+                    }
+                }
+                (Some(_), None) => {
+                    edits.push(Edit::Delete { key });
+                    continue;
+                }
+                (None, Some(their_node)) => {
+                    let at = path.split_last().map(|(side, parent)| Slot {
+                        parent: NodeKey::Position(parent.to_vec()),
+                        side: *side,
+                    });
+                    edits.push(Edit::Insert {
+                        key,
+                        at,
+                        value: their_node.borrow().value,
+                    });
+                }
+                (None, None) => continue,
+            }
+            for side in [Side::Right, Side::Left] {
+                let mut child_path = path.clone();
+                child_path.push(side);
+                stack.push((child(&ours, side), child(&theirs, side), child_path));
+            }
+        }
+        edits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \
+    //             4   5
+    //
+    fn test_tree() -> (Tree<i32>, Vec<Uuid>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+        let ids = [&node1, &node2, &node3, &node4, &node5]
+            .iter()
+            .map(|node| node.borrow().get_id())
+            .collect();
+        (Tree::new(node1), ids)
+    }
+
+    // Edits the test tree into:
+    //                 1
+    //                / \
+    //               2   30
+    //                  / \
+    //                 6   4
+    //
+    fn edit(tree: &mut Tree<i32>, ids: &[Uuid]) -> Uuid {
+        tree.get_by_id(ids[2]).unwrap().borrow_mut().value = 30;
+        tree.detach_node(ids[4]).unwrap();
+        let node4 = tree.remove_subtree(ids[3]).unwrap();
+        tree.graft(ids[2], Side::Right, node4).unwrap();
+        let node6 = tree.insert_left(ids[2], 6).unwrap();
+        let id6 = node6.borrow().get_id();
+        id6
+    }
+
+    #[test]
+    fn diff_and_patch_by_id() {
+        let (mut tree, ids) = test_tree();
+        let mut edited = tree.deep_clone();
+        let id6 = edit(&mut edited, &ids);
+
+        let edits = tree.diff(&edited);
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Delete {
+                    key: NodeKey::Id(ids[4])
+                },
+                Edit::Relabel {
+                    key: NodeKey::Id(ids[2]),
+                    from: 3,
+                    to: 30
+                },
+                Edit::Insert {
+                    key: NodeKey::Id(id6),
+                    at: Some(Slot {
+                        parent: NodeKey::Id(ids[2]),
+                        side: Side::Left
+                    }),
+                    value: 6
+                },
+                Edit::Move {
+                    key: NodeKey::Id(ids[3]),
+                    to: Some(Slot {
+                        parent: NodeKey::Id(ids[2]),
+                        side: Side::Right
+                    })
+                },
+            ]
+        );
+
+        tree.build_index();
+        tree.apply_patch(&edits).unwrap();
+        assert!(tree.is_identical(&edited));
+        assert!(tree.validate().is_valid());
+        assert!(tree.diff(&edited).is_empty());
+    }
+
+    #[test]
+    fn diff_and_patch_by_position() {
+        let (mut tree, ids) = test_tree();
+        let mut edited = tree.deep_clone();
+        edit(&mut edited, &ids);
+        let edited = edited.deep_clone_with_new_ids();
+
+        let edits = tree.diff_by_position(&edited);
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Delete {
+                    key: NodeKey::Position(vec![Side::Left, Side::Left])
+                },
+                Edit::Delete {
+                    key: NodeKey::Position(vec![Side::Left, Side::Right])
+                },
+                Edit::Relabel {
+                    key: NodeKey::Position(vec![Side::Right]),
+                    from: 3,
+                    to: 30
+                },
+                Edit::Insert {
+                    key: NodeKey::Position(vec![Side::Right, Side::Left]),
+                    at: Some(Slot {
+                        parent: NodeKey::Position(vec![Side::Right]),
+                        side: Side::Left
+                    }),
+                    value: 6
+                },
+                Edit::Insert {
+                    key: NodeKey::Position(vec![Side::Right, Side::Right]),
+                    at: Some(Slot {
+                        parent: NodeKey::Position(vec![Side::Right]),
+                        side: Side::Right
+                    }),
+                    value: 4
+                },
+            ]
+        );
+
+        tree.apply_patch(&edits).unwrap();
+        assert_eq!(tree, edited);
+        assert!(tree.diff_by_position(&edited).is_empty());
+    }
+
+    #[test]
+    fn replace_root() {
+        let (mut tree, _) = test_tree();
+        let other = Tree::new(TreeNode::new_rc(7, None, None));
+        tree.apply_patch(&tree.diff(&other)).unwrap();
+        assert!(tree.is_identical(&other));
+
+        let missing = [Edit::Delete {
+            key: NodeKey::Id(Uuid::nil()),
+        }];
+        assert_eq!(
+            tree.apply_patch(&missing).err(),
+            Some("Attempted to patch a node that is not in the tree".to_string())
+        );
+    }
+
+    #[test]
+    fn rejected_patch_changes_nothing() {
+        let (mut tree, ids) = test_tree();
+        tree.build_index();
+        tree.track_sizes();
+        tree.annotate();
+
+        // Moving 5 into the slot of 4, which stays, fails only after 5 would have been cut loose.
+        let occupied = [Edit::Move {
+            key: NodeKey::Id(ids[4]),
+            to: Some(Slot {
+                parent: NodeKey::Id(ids[1]),
+                side: Side::Left,
+            }),
+        }];
+        assert_eq!(
+            tree.apply_patch(&occupied).err(),
+            Some("Attempted to patch a node into an occupied left slot".to_string())
+        );
+        let no_root = [Edit::Delete {
+            key: NodeKey::Id(ids[0]),
+        }];
+        assert_eq!(
+            tree.apply_patch(&no_root).err(),
+            Some("Attempted to remove the root without replacing it".to_string())
+        );
+
+        assert_eq!(tree.typst_string(), "([1], ([2], [4], [5]), [3])");
+        assert!(tree.validate().is_valid());
+        assert_eq!(tree.size(), 5);
+        assert!(tree.is_annotated());
+
+        // A slot freed by the same patch can be filled.
+        let swap = [
+            Edit::Delete {
+                key: NodeKey::Id(ids[3]),
+            },
+            Edit::Move {
+                key: NodeKey::Id(ids[4]),
+                to: Some(Slot {
+                    parent: NodeKey::Id(ids[1]),
+                    side: Side::Left,
+                }),
+            },
+        ];
+        tree.apply_patch(&swap).unwrap();
+        assert_eq!(tree.typst_string(), "([1], ([2], [5], ), [3])");
+        assert!(tree.validate().is_valid());
+        assert_eq!(tree.size(), 4);
+    }
+
+    #[test]
+    fn rejects_cycles_and_double_placements() {
+        let (mut tree, ids) = test_tree();
+        tree.track_sizes();
+        let slot = |parent: usize, side| {
+            Some(Slot {
+                parent: NodeKey::Id(ids[parent]),
+                side,
+            })
+        };
+
+        // 2 below its own child 5.
+        let below_itself = [Edit::Move {
+            key: NodeKey::Id(ids[1]),
+            to: slot(4, Side::Right),
+        }];
+        assert_eq!(
+            tree.apply_patch(&below_itself).err(),
+            Some("Attempted to patch a node below itself".to_string())
+        );
+        // 5 put back under 2, with 2 under 5.
+        let swapped = [
+            Edit::Move {
+                key: NodeKey::Id(ids[4]),
+                to: slot(1, Side::Right),
+            },
+            Edit::Move {
+                key: NodeKey::Id(ids[1]),
+                to: slot(4, Side::Left),
+            },
+        ];
+        assert_eq!(
+            tree.apply_patch(&swapped).err(),
+            Some("Attempted to patch a node below itself".to_string())
+        );
+
+        let twice = [
+            Edit::Move {
+                key: NodeKey::Id(ids[3]),
+                to: slot(2, Side::Left),
+            },
+            Edit::Move {
+                key: NodeKey::Id(ids[3]),
+                to: slot(2, Side::Right),
+            },
+        ];
+        assert_eq!(
+            tree.apply_patch(&twice).err(),
+            Some("Attempted to patch the same node into two places".to_string())
+        );
+        let insert = |side| Edit::Insert {
+            key: NodeKey::Position(vec![Side::Right, Side::Left]),
+            at: slot(2, side),
+            value: 6,
+        };
+        assert_eq!(
+            tree.apply_patch(&[insert(Side::Left), insert(Side::Right)])
+                .err(),
+            Some("Attempted to insert two nodes with the same key".to_string())
+        );
+
+        assert_eq!(tree.typst_string(), "([1], ([2], [4], [5]), [3])");
+        assert!(tree.validate().is_valid());
+        assert_eq!(tree.size(), 5);
+
+        // Moving 2 below 5 is fine once 5 is moved out from below 2.
+        let rotated = [
+            Edit::Move {
+                key: NodeKey::Id(ids[4]),
+                to: None,
+            },
+            Edit::Move {
+                key: NodeKey::Id(ids[0]),
+                to: slot(4, Side::Left),
+            },
+            Edit::Move {
+                key: NodeKey::Id(ids[1]),
+                to: slot(0, Side::Left),
+            },
+        ];
+        tree.apply_patch(&rotated).unwrap();
+        assert_eq!(tree.typst_string(), "([5], ([1], ([2], [4], ), [3]), )");
+        assert!(tree.validate().is_valid());
+        assert_eq!(tree.size(), 5);
+    }
+
+    #[test]
+    fn highlight_changes() {
+        let (tree, ids) = test_tree();
+        let mut edited = tree.deep_clone();
+        edit(&mut edited, &ids);
+        let edits = tree.diff(&edited);
+
+        let removed = tree.touched_by(&edits);
+        assert_eq!(
+            tree.typst_string_highlighted(&removed),
+            "([1], ([2], [#text(fill: red)[4]], [#text(fill: red)[5]]), [#text(fill: red)[3]])"
+        );
+        let added = edited.touched_by(&edits);
+        assert_eq!(
+            edited.terminal_string_highlighted(16, &added),
+            "        1\n    2     *30*\n         *6* *4*"
+        );
+    }
+}
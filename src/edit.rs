@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Display};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// Which child slot of a node to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+impl<T: Sized + Copy> TreeNode<T> {
+    /// The child on the given side.
+    pub fn child(&self, side: Side) -> &Option<TreeNodeRef<T>> {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    /// Mutable access to the child slot on the given side.
+    pub fn child_mut(&mut self, side: Side) -> &mut Option<TreeNodeRef<T>> {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Adds a new leaf holding `value` as the child on `side` of the node with id `parent_id`,
+    /// returning the new node. Fails if the parent is not in the tree or already has a child on
+    /// that side.
+    pub fn insert_child(
+        &mut self,
+        parent_id: Uuid,
+        side: Side,
+        value: T,
+    ) -> Result<TreeNodeRef<T>, String> {
+        self.debug_validate();
+        let parent = self.get_by_id(parent_id).ok_or_else(|| {
+            "Attempted to add a child to a node that is not in the tree".to_string()
+        })?;
+        if parent.borrow().child(side).is_some() {
+            return Err(format!(
+                "Attempted to add a {} child to a node that already has one",
+                side
+            ));
+        }
+
+        let node = TreeNode::new_rc(value, None, None);
+        *parent.borrow_mut().child_mut(side) = Some(node.clone());
+        self.index_subtree(&node, Some(parent_id));
+        self.sizes_attached(&node, parent_id);
+        self.invalidate_annotations();
+        Ok(node)
+    }
+
+    /// Adds a new leaf as the left child of the node with id `parent_id`.
+    pub fn insert_left(&mut self, parent_id: Uuid, value: T) -> Result<TreeNodeRef<T>, String> {
+        self.insert_child(parent_id, Side::Left, value)
+    }
+
+    /// Adds a new leaf as the right child of the node with id `parent_id`.
+    pub fn insert_right(&mut self, parent_id: Uuid, value: T) -> Result<TreeNodeRef<T>, String> {
+        self.insert_child(parent_id, Side::Right, value)
+    }
+
+    /// Adds a new leaf in the first free child slot of the node with id `parent_id`, left before
+    /// right, like `TreeNode::add_leaf`.
+    pub fn insert_leaf(&mut self, parent_id: Uuid, value: T) -> Result<TreeNodeRef<T>, String> {
+        let parent = self.get_by_id(parent_id).ok_or_else(|| {
+            "Attempted to add a leaf to a node that is not in the tree".to_string()
+        })?;
+        let node = parent.borrow();
+        let side = if node.left.is_none() {
+            Side::Left
+        } else if node.right.is_none() {
+            Side::Right
+        } else {
+            return Err("Attempted to add a leaf to a full node".to_string());
+        };
+        drop(node);
+        self.insert_child(parent_id, side, value)
+    }
+
+    /// Cuts the node with the given id, together with all of its descendants, out of the tree
+    /// and returns it. The root cannot be detached.
+    pub fn detach_node(&mut self, id: Uuid) -> Result<TreeNodeRef<T>, String> {
+        self.debug_validate();
+        let (parent, side) = self.locate(id, "detach")?;
+        let node = parent
+            .borrow_mut()
+            .child_mut(side)
+            .take()
+            .expect("located child is present");
+        let parent_id = parent.borrow().id;
+        self.unindex_subtree(&node);
+        self.sizes_detached(&node, parent_id);
+        self.invalidate_annotations();
+        Ok(node)
+    }
+
+    /// Like `Tree::detach_node`, but returns the detached subtree as a tree of its own.
+    pub fn remove_subtree(&mut self, id: Uuid) -> Result<Tree<T>, String> {
+        self.detach_node(id).map(Tree::new)
+    }
+
+    /// Attaches `subtree` as the child on `side` of the node with id `parent_id`. Fails if the
+    /// parent is not in the tree, already has a child on that side, or if any node of `subtree` is
+    /// already part of this tree, since attaching it would make that node appear twice or create a
+    /// cycle. Also fails if a node of `subtree` has the id of a node in this tree, as a copy made
+    /// with `Tree::deep_clone` does.
+    pub fn graft(&mut self, parent_id: Uuid, side: Side, subtree: Tree<T>) -> Result<(), String> {
+        self.debug_validate();
+        subtree.debug_validate();
+        let parent = self
+            .get_by_id(parent_id)
+            .ok_or_else(|| "Attempted to graft onto a node that is not in the tree".to_string())?;
+        if parent.borrow().child(side).is_some() {
+            return Err(format!(
+                "Attempted to graft onto a node that already has a {} child",
+                side
+            ));
+        }
+        self.check_disjoint(&subtree.root, None)?;
+
+        *parent.borrow_mut().child_mut(side) = Some(subtree.root.clone());
+        self.index_subtree(&subtree.root, Some(parent_id));
+        self.sizes_attached(&subtree.root, parent_id);
+        self.invalidate_annotations();
+        Ok(())
+    }
+
+    /// Puts `subtree` in the place of the node with the given id and returns the subtree that was
+    /// there. Replacing the root swaps out the whole tree. Fails if the id is not in the tree, if
+    /// `subtree` shares any nodes with this tree, or if it has ids of nodes that stay in the tree.
+    pub fn replace_subtree(&mut self, id: Uuid, subtree: Tree<T>) -> Result<Tree<T>, String> {
+        self.debug_validate();
+        subtree.debug_validate();
+
+        if self.root.borrow().id == id {
+            self.check_disjoint(&subtree.root, Some(&self.root))?;
+            let old_root = std::mem::replace(&mut self.root, subtree.root);
+            if self.has_index() {
+                self.build_index();
+            }
+            self.refresh_sizes();
+            self.invalidate_annotations();
+            return Ok(Tree::new(old_root));
+        }
+
+        let (parent, side) = self.locate(id, "replace")?;
+        let replaced = parent
+            .borrow()
+            .child(side)
+            .clone()
+            .expect("located child is present");
+        self.check_disjoint(&subtree.root, Some(&replaced))?;
+        let parent_id = parent.borrow().id;
+        let old = parent
+            .borrow_mut()
+            .child_mut(side)
+            .replace(subtree.root.clone())
+            .expect("located child is present");
+        self.unindex_subtree(&old);
+        self.index_subtree(&subtree.root, Some(parent_id));
+        self.sizes_replaced(&old, &subtree.root, parent_id);
+        self.invalidate_annotations();
+        Ok(Tree::new(old))
+    }
+
+    /// The parent of the node with the given id and the side the node hangs on. `verb` names the
+    /// operation in the error messages.
+    fn locate(&self, id: Uuid, verb: &str) -> Result<(TreeNodeRef<T>, Side), String> {
+        if self.root.borrow().id == id {
+            return Err(format!("Attempted to {} the root of the tree", verb));
+        }
+        let parent = self
+            .parent_of(id)
+            .ok_or_else(|| format!("Attempted to {} a node that is not in the tree", verb))?;
+        let side = match &parent.borrow().left {
+            Some(left) if left.borrow().id == id => Side::Left,
+            _ => Side::Right,
+        };
+        Ok((parent, side))
+    }
+
+    /// Fails if any node of `subtree` is also a node of this tree, or has the id of one. Nodes in
+    /// the subtree of `replaced`, which is about to leave the tree, may share ids with `subtree`.
+    fn check_disjoint(
+        &self,
+        subtree: &TreeNodeRef<T>,
+        replaced: Option<&TreeNodeRef<T>>,
+    ) -> Result<(), String> {
+        let leaving: HashSet<*const _> = replaced
+            .map(|replaced| {
+                Tree::new(replaced.clone())
+                    .pre_order_with_parents()
+                    .iter()
+                    .map(|(node_ref, _)| Rc::as_ptr(node_ref))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut in_tree: HashSet<*const _> = HashSet::new();
+        let mut staying_ids: HashSet<Uuid> = HashSet::new();
+        for (node_ref, _) in self.pre_order_with_parents() {
+            in_tree.insert(Rc::as_ptr(&node_ref));
+            if !leaving.contains(&Rc::as_ptr(&node_ref)) {
+                staying_ids.insert(node_ref.borrow().id);
+            }
+        }
+        for (node_ref, _) in Tree::new(subtree.clone()).pre_order_with_parents() {
+            if in_tree.contains(&Rc::as_ptr(&node_ref)) {
+                return Err(
+                    "Attempted to attach a node that is already part of the tree".to_string(),
+                );
+            }
+            if staying_ids.contains(&node_ref.borrow().id) {
+                return Err(
+                    "Attempted to attach a node whose id is already in the tree".to_string()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_children() {
+        let mut tree = Tree::new(TreeNode::new_rc(1, None, None));
+        let root_id = tree.root.borrow().get_id();
+        tree.annotate();
+
+        let node2 = tree.insert_left(root_id, 2).unwrap();
+        assert!(!tree.is_annotated());
+        tree.insert_right(root_id, 3).unwrap();
+        let id2 = node2.borrow().get_id();
+        tree.insert_leaf(id2, 4).unwrap();
+        tree.insert_leaf(id2, 5).unwrap();
+        assert_eq!(tree.typst_string(), "([1], ([2], [4], [5]), [3])");
+
+        assert_eq!(
+            tree.insert_left(root_id, 6).err(),
+            Some("Attempted to add a left child to a node that already has one".to_string())
+        );
+        assert_eq!(
+            tree.insert_leaf(id2, 6).err(),
+            Some("Attempted to add a leaf to a full node".to_string())
+        );
+        assert!(tree.insert_left(Uuid::nil(), 6).is_err());
+    }
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \
+    //             4   5
+    //
+    fn test_tree() -> (Tree<i32>, Vec<TreeNodeRef<i32>>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+        (
+            Tree::new_indexed(node1.clone()),
+            vec![node1, node2, node3, node4, node5],
+        )
+    }
+
+    #[test]
+    fn remove_and_graft() {
+        let (mut tree, nodes) = test_tree();
+        let id = |index: usize| nodes[index].borrow().get_id();
+
+        let removed = tree.remove_subtree(id(1)).unwrap();
+        assert_eq!(tree.typst_string(), "([1], [3])");
+        assert_eq!(removed.typst_string(), "([2], [4], [5])");
+        assert!(tree.get_by_id(id(3)).is_none());
+        assert_eq!(
+            tree.remove_subtree(id(0)).err(),
+            Some("Attempted to detach the root of the tree".to_string())
+        );
+        assert!(tree.detach_node(id(1)).is_err());
+
+        tree.graft(id(2), Side::Right, removed).unwrap();
+        assert_eq!(tree.typst_string(), "([1], ([3], ([2], [4], [5])))");
+        assert!(Rc::ptr_eq(&tree.parent_of(id(1)).unwrap(), &nodes[2]));
+        assert_eq!(tree.depth_of(&nodes[4]), Some(3));
+
+        // Grafting a node that is already in the tree would attach it in two places, and
+        // grafting an ancestor below its descendant would create a cycle.
+        let err = Some("Attempted to attach a node that is already part of the tree".to_string());
+        let shared = Tree::new(nodes[3].clone());
+        assert_eq!(tree.graft(id(2), Side::Left, shared).err(), err);
+        let ancestor = Tree::new(nodes[2].clone());
+        assert_eq!(tree.graft(id(4), Side::Left, ancestor).err(), err);
+
+        // A copy that kept its ids would leave two nodes with the same id.
+        let copy = tree.deep_clone();
+        assert_eq!(
+            tree.graft(id(4), Side::Left, copy).err(),
+            Some("Attempted to attach a node whose id is already in the tree".to_string())
+        );
+        assert!(tree.validate().is_valid());
+    }
+
+    #[test]
+    fn replace_subtree() {
+        let (mut tree, nodes) = test_tree();
+        let id = |index: usize| nodes[index].borrow().get_id();
+
+        let replacement = Tree::new(TreeNode::new_rc(6, None, None));
+        let old = tree.replace_subtree(id(1), replacement).unwrap();
+        assert_eq!(tree.typst_string(), "([1], [6], [3])");
+        assert_eq!(old.typst_string(), "([2], [4], [5])");
+        assert!(tree.get_by_id(id(4)).is_none());
+
+        let old_root = tree.replace_subtree(id(0), old).unwrap();
+        assert_eq!(tree.typst_string(), "([2], [4], [5])");
+        assert_eq!(old_root.typst_string(), "([1], [6], [3])");
+        assert!(Rc::ptr_eq(&tree.parent_of(id(4)).unwrap(), &nodes[1]));
+
+        let shared = Tree::new(nodes[3].clone());
+        assert!(tree.replace_subtree(id(4), shared).is_err());
+        assert_eq!(
+            tree.replace_subtree(Uuid::nil(), Tree::new(TreeNode::new_rc(7, None, None)))
+                .err(),
+            Some("Attempted to replace a node that is not in the tree".to_string())
+        );
+
+        // Ids of the replaced subtree may come back, other ids of the tree may not.
+        let copy = Tree::new(nodes[1].clone()).deep_clone();
+        tree.replace_subtree(id(1), copy).unwrap();
+        assert_eq!(tree.typst_string(), "([2], [4], [5])");
+        let copy = tree.deep_clone();
+        assert_eq!(
+            tree.replace_subtree(id(4), copy).err(),
+            Some("Attempted to attach a node whose id is already in the tree".to_string())
+        );
+        assert!(tree.validate().is_valid());
+    }
+}
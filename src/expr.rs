@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::visitor::{TreeVisitor, VisitFlow};
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// A binary arithmetic operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl BinaryOp {
+    /// The ASCII symbol used by the parser and the prefix, postfix and infix forms.
+    pub fn symbol(&self) -> char {
+        match self {
+            BinaryOp::Add => '+',
+            BinaryOp::Sub => '-',
+            BinaryOp::Mul => '*',
+            BinaryOp::Div => '/',
+            BinaryOp::Pow => '^',
+        }
+    }
+
+    pub fn apply(&self, left: f64, right: f64) -> f64 {
+        match self {
+            BinaryOp::Add => left + right,
+            BinaryOp::Sub => left - right,
+            BinaryOp::Mul => left * right,
+            BinaryOp::Div => left / right,
+            BinaryOp::Pow => left.powf(right),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Add | BinaryOp::Sub => 1,
+            BinaryOp::Mul | BinaryOp::Div => 2,
+            BinaryOp::Pow => 3,
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<BinaryOp> {
+        match symbol {
+            '+' => Some(BinaryOp::Add),
+            '-' => Some(BinaryOp::Sub),
+            '*' => Some(BinaryOp::Mul),
+            '/' => Some(BinaryOp::Div),
+            '^' => Some(BinaryOp::Pow),
+            _ => None,
+        }
+    }
+}
+
+/// The payload of an expression tree node. Operators have exactly two children, numbers and
+/// variables none. Variables are single letters so that the payload stays `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExprNode {
+    Number(f64),
+    Variable(char),
+    Op(BinaryOp),
+}
+
+/// Uses `×`, `÷` and `−` for the operators, which read well in the terminal and, unlike `*` and
+/// `-`, have no meaning in Typst markup, so `Tree::typst_string` renders expressions as they are.
+impl Display for ExprNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprNode::Number(number) => write!(f, "{}", number),
+            ExprNode::Variable(name) => write!(f, "{}", name),
+            ExprNode::Op(BinaryOp::Mul) => write!(f, "×"),
+            ExprNode::Op(BinaryOp::Div) => write!(f, "÷"),
+            ExprNode::Op(BinaryOp::Sub) => write!(f, "−"),
+            ExprNode::Op(op) => write!(f, "{}", op.symbol()),
+        }
+    }
+}
+
+impl ExprNode {
+    /// The token for this node in the prefix, postfix and infix forms.
+    fn token(&self) -> String {
+        match self {
+            ExprNode::Op(op) => op.symbol().to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+enum Token {
+    Operand(ExprNode),
+    Op(BinaryOp),
+    /// A `-` where an operand is expected. Produced by the parser, not the tokenizer.
+    Negate,
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let token = if c.is_ascii_digit() || c == '.' {
+            let mut number = String::from(c);
+            while let Some(&next) = chars.peek() {
+                if !(next.is_ascii_digit() || next == '.') {
+                    break;
+                }
+                number.push(next);
+                chars.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| format!("Attempted to parse an invalid number '{}'", number))?;
+            Token::Operand(ExprNode::Number(number))
+        } else if c.is_alphabetic() {
+            Token::Operand(ExprNode::Variable(c))
+        } else if let Some(op) = BinaryOp::from_symbol(c) {
+            Token::Op(op)
+        } else if c == '(' {
+            Token::LeftParen
+        } else if c == ')' {
+            Token::RightParen
+        } else {
+            return Err(format!(
+                "Attempted to parse an unexpected character '{}'",
+                c
+            ));
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Pops the two topmost operands and pushes them back joined by `op`.
+fn apply_op(output: &mut Vec<TreeNodeRef<ExprNode>>, op: BinaryOp) {
+    let right = output.pop().expect("operators follow two operands");
+    let left = output.pop().expect("operators follow two operands");
+    output.push(TreeNode::new_rc(ExprNode::Op(op), Some(left), Some(right)));
+}
+
+/// Pops the topmost operand and pushes back its negation: a negative number for a number, and
+/// `(-1 * operand)` for anything else.
+fn negate(output: &mut Vec<TreeNodeRef<ExprNode>>) {
+    let operand = output.pop().expect("negation follows an operand");
+    let value = operand.borrow().value;
+    output.push(match value {
+        ExprNode::Number(number) => TreeNode::new_rc(ExprNode::Number(-number), None, None),
+        _ => {
+            let minus_one = TreeNode::new_rc(ExprNode::Number(-1.0), None, None);
+            TreeNode::new_rc(ExprNode::Op(BinaryOp::Mul), Some(minus_one), Some(operand))
+        }
+    });
+}
+
+impl Tree<ExprNode> {
+    /// Parses an infix expression such as `2 * (x + 1) ^ 2`. Supports numbers, single-letter
+    /// variables, `+ - * / ^` with the usual precedence, `^` binding to the right, and
+    /// parentheses. There is no implicit multiplication.
+    ///
+    /// A `-` where an operand is expected negates the operand that follows it, binding tighter
+    /// than any other operator, so `-2 ^ 2` is `(-2) ^ 2`. Negated numbers become negative
+    /// numbers, anything else is multiplied by -1: `-x` parses as `(-1 * x)`.
+    ///
+    /// Uses the shunting-yard algorithm, so deeply nested input doesn't grow the call stack.
+    pub fn parse_expr(input: &str) -> Result<Self, String> {
+        let mut output: Vec<TreeNodeRef<ExprNode>> = Vec::new();
+        let mut pending: Vec<Token> = Vec::new();
+        let mut expect_operand = true;
+        for token in tokenize(input)? {
+            match token {
+                Token::Operand(node) => {
+                    if !expect_operand {
+                        return Err("Attempted to parse two operands in a row".to_string());
+                    }
+                    output.push(TreeNode::new_rc(node, None, None));
+                    expect_operand = false;
+                }
+                Token::Op(BinaryOp::Sub) if expect_operand => pending.push(Token::Negate),
+                Token::Negate => unreachable!("the tokenizer does not produce negations"),
+                Token::Op(op) => {
+                    if expect_operand {
+                        return Err(format!(
+                            "Attempted to parse '{}' where an operand was expected",
+                            op.symbol()
+                        ));
+                    }
+                    loop {
+                        match pending.last() {
+                            Some(Token::Negate) => negate(&mut output),
+                            Some(Token::Op(top)) => {
+                                let binds_left = op != BinaryOp::Pow;
+                                if top.precedence() > op.precedence()
+                                    || (top.precedence() == op.precedence() && binds_left)
+                                {
+                                    apply_op(&mut output, *top);
+                                } else {
+                                    break;
+                                }
+                            }
+                            _ => break,
+                        }
+                        pending.pop();
+                    }
+                    pending.push(Token::Op(op));
+                    expect_operand = true;
+                }
+                Token::LeftParen => {
+                    if !expect_operand {
+                        return Err("Attempted to parse two operands in a row".to_string());
+                    }
+                    pending.push(Token::LeftParen);
+                }
+                Token::RightParen => {
+                    if expect_operand {
+                        return Err(
+                            "Attempted to parse ')' where an operand was expected".to_string()
+                        );
+                    }
+                    loop {
+                        match pending.pop() {
+                            Some(Token::Op(op)) => apply_op(&mut output, op),
+                            Some(Token::Negate) => negate(&mut output),
+                            Some(_) => break,
+                            None => return Err("Attempted to parse an unmatched ')'".to_string()),
+                        }
+                    }
+                }
+            }
+        }
+        if expect_operand {
+            return Err("Attempted to parse an incomplete expression".to_string());
+        }
+        while let Some(token) = pending.pop() {
+            match token {
+                Token::Op(op) => apply_op(&mut output, op),
+                Token::Negate => negate(&mut output),
+                _ => return Err("Attempted to parse an unmatched '('".to_string()),
+            }
+        }
+        let root = output
+            .pop()
+            .expect("a complete expression leaves one operand");
+        Ok(Tree::new(root))
+    }
+
+    /// Evaluates the expression, looking variables up in `env`. Fails on unbound variables and on
+    /// trees that are not well-formed expressions, e.g. edited by hand. Division by zero follows
+    /// `f64` and gives an infinity or NaN.
+    pub fn evaluate(&self, env: &HashMap<char, f64>) -> Result<f64, String> {
+        // Operands come before their operator in post-order, so a value stack suffices.
+        let mut values: Vec<f64> = Vec::new();
+        for node_ref in self.post_order_nodes() {
+            let node = node_ref.borrow();
+            let children = node.count_children();
+            let value = match node.value {
+                ExprNode::Number(number) if children == 0 => number,
+                ExprNode::Variable(name) if children == 0 => *env.get(&name).ok_or_else(|| {
+                    format!(
+                        "Attempted to evaluate an expression with unbound variable '{}'",
+                        name
+                    )
+                })?,
+                ExprNode::Op(op) if children == 2 => {
+                    let right = values.pop().expect("operands are evaluated first");
+                    let left = values.pop().expect("operands are evaluated first");
+                    op.apply(left, right)
+                }
+                _ => return Err("Attempted to evaluate a malformed expression".to_string()),
+            };
+            values.push(value);
+        }
+        Ok(values.pop().expect("the root is evaluated last"))
+    }
+
+    /// The expression in prefix (Polish) notation, tokens separated by spaces.
+    pub fn to_prefix(&self) -> String {
+        self.fold_pre_order(Vec::new(), push_token).join(" ")
+    }
+
+    /// The expression in postfix (reverse Polish) notation, tokens separated by spaces.
+    pub fn to_postfix(&self) -> String {
+        self.fold_post_order(Vec::new(), push_token).join(" ")
+    }
+
+    /// The expression in infix notation with every operation in parentheses, e.g.
+    /// `((1 + 2) * x)`. The result parses back to the same tree, negative numbers included.
+    pub fn to_infix(&self) -> String {
+        let mut visitor = InfixVisitor { out: String::new() };
+        self.walk(&mut visitor);
+        visitor.out
+    }
+}
+
+fn push_token(mut tokens: Vec<String>, node: &ExprNode) -> Vec<String> {
+    tokens.push(node.token());
+    tokens
+}
+
+struct InfixVisitor {
+    out: String,
+}
+
+impl TreeVisitor<ExprNode> for InfixVisitor {
+    fn enter_node(&mut self, node: &TreeNode<ExprNode>, _depth: usize) -> VisitFlow {
+        match node.value {
+            ExprNode::Op(_) => self.out.push('('),
+            other => self.out.push_str(&other.token()),
+        }
+        VisitFlow::Continue
+    }
+
+    fn exit_node(&mut self, node: &TreeNode<ExprNode>, _depth: usize) -> VisitFlow {
+        if let ExprNode::Op(_) = node.value {
+            self.out.push(')');
+        }
+        VisitFlow::Continue
+    }
+
+    fn visit_right_edge(
+        &mut self,
+        parent: &TreeNode<ExprNode>,
+        _child: &TreeNode<ExprNode>,
+    ) -> VisitFlow {
+        self.out.push_str(&format!(" {} ", parent.value.token()));
+        VisitFlow::Continue
+    }
+}
+
+impl FromStr for Tree<ExprNode> {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Tree::parse_expr(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print() {
+        let tree = Tree::parse_expr("2 * (x + 1.5) ^ 2 ^ y - 3 / z").unwrap();
+        assert_eq!(tree.to_infix(), "((2 * ((x + 1.5) ^ (2 ^ y))) - (3 / z))");
+        assert_eq!(tree.to_prefix(), "- * 2 ^ + x 1.5 ^ 2 y / 3 z");
+        assert_eq!(tree.to_postfix(), "2 x 1.5 + 2 y ^ ^ * 3 z / -");
+        assert_eq!(Tree::parse_expr(&tree.to_infix()).unwrap(), tree);
+        assert_eq!(
+            "1 - 2 - 3".parse::<Tree<ExprNode>>().unwrap().to_infix(),
+            "((1 - 2) - 3)"
+        );
+        assert_eq!(
+            Tree::parse_expr("a * b + c").unwrap().typst_string(),
+            "([+], ([×], [a], [b]), [c])"
+        );
+    }
+
+    #[test]
+    fn unary_minus() {
+        let tree = Tree::parse_expr("-2 * x - -1.5 ^ 2").unwrap();
+        assert_eq!(tree.to_infix(), "((-2 * x) - (-1.5 ^ 2))");
+        assert_eq!(tree.to_prefix(), "- * -2 x ^ -1.5 2");
+        assert_eq!(Tree::parse_expr(&tree.to_infix()).unwrap(), tree);
+
+        let infix = |input: &str| Tree::parse_expr(input).unwrap().to_infix();
+        assert_eq!(infix("-x"), "(-1 * x)");
+        assert_eq!(infix("-(1 + 2) * 3"), "((-1 * (1 + 2)) * 3)");
+        assert_eq!(infix("2 ^ -x ^ 2"), "(2 ^ ((-1 * x) ^ 2))");
+        assert_eq!(infix("--2"), "2");
+        assert_eq!(
+            Tree::parse_expr("-2 ^ 2")
+                .unwrap()
+                .evaluate(&HashMap::new()),
+            Ok(4.0)
+        );
+        assert_eq!(
+            Tree::parse_expr("1 - ").err(),
+            Some("Attempted to parse an incomplete expression".to_string())
+        );
+    }
+
+    #[test]
+    fn evaluate() {
+        let tree = Tree::parse_expr("2 * (x + 1) ^ 2 - y / 4").unwrap();
+        let env = HashMap::from([('x', 2.0), ('y', 8.0)]);
+        assert_eq!(tree.evaluate(&env), Ok(16.0));
+        assert_eq!(
+            tree.evaluate(&HashMap::new()).err(),
+            Some("Attempted to evaluate an expression with unbound variable 'x'".to_string())
+        );
+
+        tree.root.borrow_mut().right = None;
+        assert_eq!(
+            tree.evaluate(&env).err(),
+            Some("Attempted to evaluate a malformed expression".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |input: &str| Tree::parse_expr(input).err().unwrap();
+        assert_eq!(error(""), "Attempted to parse an incomplete expression");
+        assert_eq!(error("1 +"), "Attempted to parse an incomplete expression");
+        assert_eq!(error("2 x"), "Attempted to parse two operands in a row");
+        assert_eq!(error("2 (x)"), "Attempted to parse two operands in a row");
+        assert_eq!(
+            error("* 2"),
+            "Attempted to parse '*' where an operand was expected"
+        );
+        assert_eq!(
+            error("()"),
+            "Attempted to parse ')' where an operand was expected"
+        );
+        assert_eq!(error("(1 + 2"), "Attempted to parse an unmatched '('");
+        assert_eq!(error("1 + 2)"), "Attempted to parse an unmatched ')'");
+        assert_eq!(
+            error("1.2.3"),
+            "Attempted to parse an invalid number '1.2.3'"
+        );
+        assert_eq!(
+            error("1 % 2"),
+            "Attempted to parse an unexpected character '%'"
+        );
+    }
+}
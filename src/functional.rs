@@ -0,0 +1,257 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use crate::edit::Side;
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Every node of the tree in in-order: left subtree, node, right subtree.
+    pub(crate) fn in_order_nodes(&self) -> Vec<TreeNodeRef<T>> {
+        let mut order: Vec<TreeNodeRef<T>> = Vec::new();
+        let mut stack: Vec<TreeNodeRef<T>> = Vec::new();
+        let mut current = Some(self.root.clone());
+        while current.is_some() || !stack.is_empty() {
+            while let Some(node_ref) = current {
+                current = node_ref.borrow().left.clone();
+                stack.push(node_ref);
+            }
+            let node_ref = stack.pop().expect("stack is not empty");
+            current = node_ref.borrow().right.clone();
+            order.push(node_ref);
+        }
+        order
+    }
+
+    /// Every node of the tree in post-order: left subtree, right subtree, node.
+    pub(crate) fn post_order_nodes(&self) -> Vec<TreeNodeRef<T>> {
+        // Visiting node, right, left and reversing the result gives left, right, node.
+        let mut order: Vec<TreeNodeRef<T>> = Vec::new();
+        let mut stack: Vec<TreeNodeRef<T>> = vec![self.root.clone()];
+        while let Some(current) = stack.pop() {
+            {
+                let node = current.borrow();
+                stack.extend(node.left.clone());
+                stack.extend(node.right.clone());
+            }
+            order.push(current);
+        }
+        order.reverse();
+        order
+    }
+
+    /// Builds a tree of the same shape, with the value of every node computed by `f` from the
+    /// original node and its position in pre-order. The new nodes keep the ids of the originals.
+    fn rebuild<U: Sized + Copy + Debug + Display>(
+        &self,
+        f: impl Fn(usize, &TreeNode<T>) -> U,
+    ) -> Tree<U> {
+        let order = self.pre_order_with_parents();
+        let mut built: HashMap<*const RefCell<TreeNode<T>>, TreeNodeRef<U>> = HashMap::new();
+        for (position, (node_ref, _)) in order.iter().enumerate().rev() {
+            let node = node_ref.borrow();
+            let mut take = |child: &Option<TreeNodeRef<T>>| {
+                child
+                    .as_ref()
+                    .map(|child| built.remove(&Rc::as_ptr(child)).expect("child is built"))
+            };
+            let (left, right) = (take(&node.left), take(&node.right));
+            let copy = TreeNode::new_with_id(f(position, &node), left, right, node.id);
+            built.insert(Rc::as_ptr(node_ref), Rc::new(RefCell::new(copy)));
+        }
+        Tree::new(
+            built
+                .remove(&Rc::as_ptr(&self.root))
+                .expect("root is built"),
+        )
+    }
+
+    /// A tree of the same shape with `f` applied to every value. The new nodes keep the ids of
+    /// the originals, so ids found in one tree can be looked up in the other.
+    pub fn map<U: Sized + Copy + Debug + Display>(&self, f: impl Fn(&T) -> U) -> Tree<U> {
+        self.rebuild(|_, node| f(&node.value))
+    }
+
+    /// Combines two trees of the same shape value by value, keeping the ids of this tree's
+    /// nodes. Fails if the shapes differ.
+    pub fn zip_with<U, V>(
+        &self,
+        other: &Tree<U>,
+        f: impl Fn(&T, &U) -> V,
+    ) -> Result<Tree<V>, String>
+    where
+        U: Sized + Copy + Debug + Display,
+        V: Sized + Copy + Debug + Display,
+    {
+        let ours = self.pre_order_with_parents();
+        let theirs = other.pre_order_with_parents();
+        let same_shape = ours.len() == theirs.len()
+            && ours.iter().zip(&theirs).all(|((a, _), (b, _))| {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.left.is_some() == b.left.is_some() && a.right.is_some() == b.right.is_some()
+            });
+        if !same_shape {
+            return Err("Attempted to zip trees of different shapes".to_string());
+        }
+        let values: Vec<U> = theirs
+            .iter()
+            .map(|(node_ref, _)| node_ref.borrow().value)
+            .collect();
+        Ok(self.rebuild(|position, node| f(&node.value, &values[position])))
+    }
+
+    /// Folds the values of the tree in pre-order: node, left subtree, right subtree.
+    pub fn fold_pre_order<A>(&self, init: A, mut f: impl FnMut(A, &T) -> A) -> A {
+        self.pre_order_with_parents()
+            .iter()
+            .fold(init, |acc, (node_ref, _)| f(acc, &node_ref.borrow().value))
+    }
+
+    /// Folds the values of the tree in in-order: left subtree, node, right subtree.
+    pub fn fold_in_order<A>(&self, init: A, mut f: impl FnMut(A, &T) -> A) -> A {
+        self.in_order_nodes()
+            .iter()
+            .fold(init, |acc, node_ref| f(acc, &node_ref.borrow().value))
+    }
+
+    /// Folds the values of the tree in post-order: left subtree, right subtree, node.
+    pub fn fold_post_order<A>(&self, init: A, mut f: impl FnMut(A, &T) -> A) -> A {
+        self.post_order_nodes()
+            .iter()
+            .fold(init, |acc, node_ref| f(acc, &node_ref.borrow().value))
+    }
+
+    /// Cuts out every subtree whose root value matches `predicate` and returns them in pre-order.
+    /// Descendants of a pruned node are removed with it and not tested on their own. Fails without
+    /// changing anything if the root matches, since a tree cannot be empty.
+    pub fn prune(&mut self, predicate: impl Fn(&T) -> bool) -> Result<Vec<Tree<T>>, String> {
+        self.debug_validate();
+        if predicate(&self.root.borrow().value) {
+            return Err("Attempted to prune the root of the tree".to_string());
+        }
+
+        // Walk in pre-order, testing each node on the way down so that pruned subtrees are never
+        // entered.
+        let mut pruned: Vec<TreeNodeRef<T>> = Vec::new();
+        let mut stack: Vec<(TreeNodeRef<T>, Side)> = Vec::new();
+        let push_children = |stack: &mut Vec<(TreeNodeRef<T>, Side)>, node_ref: &TreeNodeRef<T>| {
+            for side in [Side::Right, Side::Left] {
+                if node_ref.borrow().child(side).is_some() {
+                    stack.push((node_ref.clone(), side));
+                }
+            }
+        };
+        push_children(&mut stack, &self.root);
+        while let Some((parent, side)) = stack.pop() {
+            let child = parent
+                .borrow()
+                .child(side)
+                .clone()
+                .expect("pushed child is present");
+            if predicate(&child.borrow().value) {
+                parent.borrow_mut().child_mut(side).take();
+                pruned.push(child);
+            } else {
+                push_children(&mut stack, &child);
+            }
+        }
+
+        for subtree in &pruned {
+            self.unindex_subtree(subtree);
+        }
+        if !pruned.is_empty() {
+            self.refresh_sizes();
+            self.invalidate_annotations();
+        }
+        Ok(pruned.into_iter().map(Tree::new).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> Tree<i32> {
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, Some(node7), None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8));
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node3 = TreeNode::new_rc(3, None, Some(node6));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    fn collect(acc: Vec<i32>, value: &i32) -> Vec<i32> {
+        let mut acc = acc;
+        acc.push(*value);
+        acc
+    }
+
+    #[test]
+    fn folds() {
+        let tree = test_tree();
+        assert_eq!(tree.fold_pre_order(0, |acc, value| acc + value), 36);
+        assert_eq!(
+            tree.fold_pre_order(Vec::new(), collect),
+            vec![1, 2, 4, 5, 7, 3, 6, 8]
+        );
+        assert_eq!(
+            tree.fold_in_order(Vec::new(), collect),
+            vec![4, 2, 7, 5, 1, 3, 6, 8]
+        );
+        assert_eq!(
+            tree.fold_post_order(Vec::new(), collect),
+            vec![4, 7, 5, 2, 8, 6, 3, 1]
+        );
+    }
+
+    #[test]
+    fn map_and_zip() {
+        let tree = test_tree();
+        let even = tree.map(|value| value % 2 == 0);
+        assert_eq!(
+            even.typst_string(),
+            "([false], ([true], [true], ([false], [false], )), ([false], ([true], [true])))"
+        );
+        assert_eq!(even.root.borrow().get_id(), tree.root.borrow().get_id());
+
+        let scaled = tree.map(|value| *value as f64 / 2.0);
+        let zipped = tree
+            .zip_with(&scaled, |value, half| *value as f64 - half)
+            .unwrap();
+        assert_eq!(zipped, scaled);
+
+        let small = Tree::new(TreeNode::new_rc(1, None, None));
+        assert_eq!(
+            tree.zip_with(&small, |a, b| a + b).err(),
+            Some("Attempted to zip trees of different shapes".to_string())
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let mut tree = test_tree();
+        tree.build_index();
+        let pruned = tree.prune(|value| value % 3 == 0 || *value == 5).unwrap();
+        assert_eq!(tree.typst_string(), "([1], ([2], [4], ), )");
+        let pruned: Vec<String> = pruned.iter().map(Tree::typst_string).collect();
+        assert_eq!(pruned, vec!["([5], [7], )", "([3], ([6], [8]))"]);
+        assert!(tree.validate().is_valid());
+
+        assert_eq!(
+            tree.prune(|value| *value == 1).err(),
+            Some("Attempted to prune the root of the tree".to_string())
+        );
+    }
+}
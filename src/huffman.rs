@@ -0,0 +1,348 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::{self, Display};
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// The payload of a Huffman tree node. Leaves carry a symbol, internal nodes the combined weight
+/// of their subtree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HuffmanNode {
+    pub weight: u64,
+    pub symbol: Option<u8>,
+}
+
+/// Leaves show as `symbol: weight`. Symbols other than ASCII letters and digits are written in
+/// hex, so that they print cleanly and can't be mistaken for Typst markup.
+impl Display for HuffmanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.symbol {
+            Some(symbol) if symbol.is_ascii_alphanumeric() => {
+                write!(f, "{}: {}", symbol as char, self.weight)
+            }
+            Some(symbol) => write!(f, "{:#04x}: {}", symbol, self.weight),
+            None => write!(f, "{}", self.weight),
+        }
+    }
+}
+
+/// Packs bits into bytes, most significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().expect("a byte was pushed") |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        for shift in (0..8).rev() {
+            self.push(byte >> shift & 1 == 1);
+        }
+    }
+}
+
+/// Reads the bits written by a `BitWriter`.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    len: usize,
+}
+
+impl BitReader<'_> {
+    fn next_bit(&mut self) -> Option<bool> {
+        if self.position >= self.len {
+            return None;
+        }
+        let bit = self.bytes[self.position / 8] & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        (0..8).try_fold(0, |byte, _| Some(byte << 1 | self.next_bit()? as u8))
+    }
+}
+
+impl Tree<HuffmanNode> {
+    /// Builds a Huffman tree from `(symbol, weight)` pairs. Ties are broken by the order in which
+    /// nodes were created, so the same frequencies always give the same tree. Fails if there are
+    /// no symbols, a symbol appears twice, or the weights add up to more than `u64::MAX`.
+    pub fn huffman_from_frequencies(frequencies: &[(u8, u64)]) -> Result<Self, String> {
+        if frequencies.is_empty() {
+            return Err("Attempted to build a Huffman tree without symbols".to_string());
+        }
+        let mut nodes: Vec<TreeNodeRef<HuffmanNode>> = Vec::new();
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = BinaryHeap::new();
+        let mut seen = [false; 256];
+        for &(symbol, weight) in frequencies {
+            if std::mem::replace(&mut seen[symbol as usize], true) {
+                return Err(format!(
+                    "Attempted to build a Huffman tree with symbol {:#04x} twice",
+                    symbol
+                ));
+            }
+            let leaf = HuffmanNode {
+                weight,
+                symbol: Some(symbol),
+            };
+            heap.push(Reverse((weight, nodes.len())));
+            nodes.push(TreeNode::new_rc(leaf, None, None));
+        }
+
+        while heap.len() > 1 {
+            let Reverse((left_weight, left)) = heap.pop().expect("heap has two entries");
+            let Reverse((right_weight, right)) = heap.pop().expect("heap has two entries");
+            let weight = left_weight.checked_add(right_weight).ok_or_else(|| {
+                "Attempted to build a Huffman tree whose total weight overflows u64".to_string()
+            })?;
+            let node = TreeNode::new_rc(
+                HuffmanNode {
+                    weight,
+                    symbol: None,
+                },
+                Some(nodes[left].clone()),
+                Some(nodes[right].clone()),
+            );
+            heap.push(Reverse((weight, nodes.len())));
+            nodes.push(node);
+        }
+        let Reverse((_, root)) = heap.pop().expect("heap has the root");
+        let root = nodes.swap_remove(root);
+        Ok(Tree::new(root))
+    }
+
+    /// Builds a Huffman tree from the byte frequencies of `data`.
+    pub fn huffman_from_bytes(data: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u64; 256];
+        for &byte in data {
+            counts[byte as usize] += 1;
+        }
+        let frequencies: Vec<(u8, u64)> = (0..=255u8)
+            .filter(|&byte| counts[byte as usize] > 0)
+            .map(|byte| (byte, counts[byte as usize]))
+            .collect();
+        Tree::huffman_from_frequencies(&frequencies)
+    }
+
+    /// The code of every symbol, `false` for a step to the left and `true` for one to the right.
+    /// A tree with a single symbol gives it the one-bit code `false`.
+    pub fn code_table(&self) -> HashMap<u8, Vec<bool>> {
+        let mut table: HashMap<u8, Vec<bool>> = HashMap::new();
+        let mut stack: Vec<(TreeNodeRef<HuffmanNode>, Vec<bool>)> =
+            vec![(self.root.clone(), vec![])];
+        while let Some((node_ref, code)) = stack.pop() {
+            let node = node_ref.borrow();
+            if let Some(symbol) = node.value.symbol {
+                let code = if code.is_empty() { vec![false] } else { code };
+                table.insert(symbol, code);
+                continue;
+            }
+            for (child, bit) in [(&node.left, false), (&node.right, true)] {
+                if let Some(child) = child {
+                    let mut child_code = code.clone();
+                    child_code.push(bit);
+                    stack.push((child.clone(), child_code));
+                }
+            }
+        }
+        table
+    }
+
+    /// Encodes `data`, returning the packed bits and how many of them are used. Fails on bytes
+    /// that are not in the tree.
+    pub fn encode(&self, data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+        let table = self.code_table();
+        let mut writer = BitWriter::default();
+        for byte in data {
+            let code = table.get(byte).ok_or_else(|| {
+                format!(
+                    "Attempted to encode byte {:#04x} which is not in the tree",
+                    byte
+                )
+            })?;
+            for &bit in code {
+                writer.push(bit);
+            }
+        }
+        Ok((writer.bytes, writer.len))
+    }
+
+    /// Decodes the first `bit_len` bits of `bits` as produced by `encode`.
+    pub fn decode(&self, bits: &[u8], bit_len: usize) -> Result<Vec<u8>, String> {
+        if bit_len > bits.len() * 8 {
+            return Err("Attempted to decode more bits than were given".to_string());
+        }
+        let mut reader = BitReader {
+            bytes: bits,
+            position: 0,
+            len: bit_len,
+        };
+        let mut out: Vec<u8> = Vec::new();
+        let mut current = self.root.clone();
+        let mut in_code = false;
+        while let Some(bit) = reader.next_bit() {
+            let next = {
+                let node = current.borrow();
+                if node.is_leaf() {
+                    // A single-symbol tree, every bit is a whole code.
+                    None
+                } else if bit {
+                    node.right.clone()
+                } else {
+                    node.left.clone()
+                }
+            };
+            if let Some(next) = next {
+                current = next;
+            }
+            let symbol = current.borrow().value.symbol;
+            if let Some(symbol) = symbol {
+                out.push(symbol);
+                current = self.root.clone();
+                in_code = false;
+            } else {
+                in_code = true;
+            }
+        }
+        if in_code {
+            return Err("Attempted to decode a truncated code".to_string());
+        }
+        Ok(out)
+    }
+
+    /// Serializes the shape and symbols of the tree in pre-order: a 0 bit for an internal node,
+    /// a 1 bit followed by the 8 bits of the symbol for a leaf. Weights are not stored.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        for (node_ref, _) in self.pre_order_with_parents() {
+            match node_ref.borrow().value.symbol {
+                Some(symbol) => {
+                    writer.push(true);
+                    writer.push_byte(symbol);
+                }
+                None => writer.push(false),
+            }
+        }
+        writer.bytes
+    }
+
+    /// Rebuilds a tree written by `serialize`. As weights are not stored, leaves get weight 0
+    /// and the result is only good for coding, not for drawing frequencies.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = BitReader {
+            bytes,
+            position: 0,
+            len: bytes.len() * 8,
+        };
+        let truncated = || "Attempted to deserialize a truncated Huffman tree".to_string();
+        let read_node = |reader: &mut BitReader| -> Result<TreeNodeRef<HuffmanNode>, String> {
+            let symbol = match reader.next_bit().ok_or_else(truncated)? {
+                true => Some(reader.next_byte().ok_or_else(truncated)?),
+                false => None,
+            };
+            Ok(TreeNode::new_rc(
+                HuffmanNode { weight: 0, symbol },
+                None,
+                None,
+            ))
+        };
+
+        let root = read_node(&mut reader)?;
+        // Internal nodes still waiting for children, the innermost last.
+        let mut open: Vec<TreeNodeRef<HuffmanNode>> = Vec::new();
+        if root.borrow().value.symbol.is_none() {
+            open.push(root.clone());
+        }
+        while let Some(parent) = open.last().cloned() {
+            let node = read_node(&mut reader)?;
+            let mut parent = parent.borrow_mut();
+            if parent.left.is_none() {
+                parent.left = Some(node.clone());
+            } else {
+                parent.right = Some(node.clone());
+                drop(parent);
+                open.pop();
+            }
+            if node.borrow().value.symbol.is_none() {
+                open.push(node);
+            }
+        }
+        Ok(Tree::new(root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_and_codes() {
+        let tree =
+            Tree::huffman_from_frequencies(&[(b'a', 5), (b'b', 2), (b'c', 1), (b'd', 1)]).unwrap();
+        assert_eq!(
+            tree.typst_string(),
+            "([9], ([4], [b: 2], ([2], [c: 1], [d: 1])), [a: 5])"
+        );
+        let table = tree.code_table();
+        assert_eq!(table[&b'a'], vec![true]);
+        assert_eq!(table[&b'b'], vec![false, false]);
+        assert_eq!(table[&b'c'], vec![false, true, false]);
+
+        assert!(Tree::huffman_from_frequencies(&[]).is_err());
+        assert_eq!(
+            Tree::huffman_from_frequencies(&[(b' ', 1), (b' ', 2)]).err(),
+            Some("Attempted to build a Huffman tree with symbol 0x20 twice".to_string())
+        );
+    }
+
+    #[test]
+    fn weight_overflow() {
+        assert_eq!(
+            Tree::huffman_from_frequencies(&[(b'a', u64::MAX), (b'b', 1)]).err(),
+            Some("Attempted to build a Huffman tree whose total weight overflows u64".to_string())
+        );
+        let tree = Tree::huffman_from_frequencies(&[(b'a', u64::MAX - 1), (b'b', 1)]).unwrap();
+        assert_eq!(tree.root.borrow().value.weight, u64::MAX);
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let data = b"abracadabra, said the wizard";
+        let tree = Tree::huffman_from_bytes(data).unwrap();
+        let (bits, bit_len) = tree.encode(data).unwrap();
+        assert!(bits.len() < data.len());
+        assert_eq!(tree.decode(&bits, bit_len).unwrap(), data);
+        assert_eq!(
+            tree.decode(&bits, bit_len - 1).err(),
+            Some("Attempted to decode a truncated code".to_string())
+        );
+        assert!(tree.encode(b"xyz").is_err());
+
+        let single = Tree::huffman_from_bytes(b"aaa").unwrap();
+        let (bits, bit_len) = single.encode(b"aaa").unwrap();
+        assert_eq!(bit_len, 3);
+        assert_eq!(single.decode(&bits, bit_len).unwrap(), b"aaa");
+    }
+
+    #[test]
+    fn serialize() {
+        let data = b"abracadabra, said the wizard";
+        let tree = Tree::huffman_from_bytes(data).unwrap();
+        let bytes = tree.serialize();
+        let restored = Tree::deserialize(&bytes).unwrap();
+        assert_eq!(restored.code_table(), tree.code_table());
+        let (bits, bit_len) = tree.encode(data).unwrap();
+        assert_eq!(restored.decode(&bits, bit_len).unwrap(), data);
+        assert!(Tree::deserialize(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+
+use uuid::Uuid;
+
+/// How `TreeNode::new` and the other node constructors pick the id of a new node.
+///
+/// The strategy is set per thread with `set_id_strategy` or, for a single block of code, with
+/// `with_id_strategy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdStrategy {
+    /// A random version 4 UUID per node. This is the default with the `random-ids` feature.
+    #[cfg(feature = "random-ids")]
+    Random,
+    /// Counts up from 1, so the n-th node created gets `Uuid::from_u128(n)`. This is the default
+    /// without the `random-ids` feature.
+    Sequential,
+    /// Version 5 UUIDs derived from the seed and a counter. The same seed always produces the same
+    /// sequence of ids, while different seeds produce ids that do not collide with each other.
+    Deterministic { seed: u64 },
+}
+
+impl Default for IdStrategy {
+    #[cfg(feature = "random-ids")]
+    fn default() -> Self {
+        IdStrategy::Random
+    }
+
+    #[cfg(not(feature = "random-ids"))]
+    fn default() -> Self {
+        IdStrategy::Sequential
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct IdGenerator {
+    strategy: IdStrategy,
+    /// Number of ids handed out on this thread. Changing the strategy does not reset it, so
+    /// counter-based ids never repeat unless `restart_id_sequence` is called.
+    counter: u64,
+}
+
+impl IdGenerator {
+    fn next_id(&mut self) -> Uuid {
+        self.counter += 1;
+        match self.strategy {
+            #[cfg(feature = "random-ids")]
+            IdStrategy::Random => Uuid::new_v4(),
+            IdStrategy::Sequential => Uuid::from_u128(self.counter as u128),
+            IdStrategy::Deterministic { seed } => {
+                let namespace = Uuid::new_v5(&Uuid::NAMESPACE_OID, &seed.to_be_bytes());
+                Uuid::new_v5(&namespace, &self.counter.to_be_bytes())
+            }
+        }
+    }
+}
+
+thread_local! {
+    static ID_GENERATOR: RefCell<IdGenerator> = RefCell::new(IdGenerator::default());
+}
+
+/// The id strategy used by node constructors on the current thread.
+pub fn id_strategy() -> IdStrategy {
+    ID_GENERATOR.with(|generator| generator.borrow().strategy)
+}
+
+/// Sets the id strategy used by node constructors on the current thread. Sequential and
+/// deterministic ids carry on counting where the thread left off, so they never repeat an id
+/// handed out earlier.
+pub fn set_id_strategy(strategy: IdStrategy) {
+    ID_GENERATOR.with(|generator| generator.borrow_mut().strategy = strategy);
+}
+
+/// Makes sequential and deterministic ids on the current thread start again from the first id,
+/// so that a snapshot test produces the same ids on every run.
+///
+/// Ids handed out afterwards repeat the ones handed out before. Do not mix nodes created before
+/// and after a restart in one tree, or nodes will share ids.
+pub fn restart_id_sequence() {
+    ID_GENERATOR.with(|generator| generator.borrow_mut().counter = 0);
+}
+
+/// Runs `f` with the given id strategy, then restores the previous strategy, also if `f`
+/// panics. Together with `restart_id_sequence` this builds reproducible trees for snapshot tests:
+///
+/// ```
+/// use binary_tree_ds::{restart_id_sequence, with_id_strategy, IdStrategy, TreeNode};
+///
+/// restart_id_sequence();
+/// let node = with_id_strategy(IdStrategy::Sequential, || TreeNode::new(1, None, None));
+/// assert_eq!(node.get_id(), uuid::Uuid::from_u128(1));
+/// ```
+pub fn with_id_strategy<R>(strategy: IdStrategy, f: impl FnOnce() -> R) -> R {
+    /// Puts the previous strategy back when dropped, including during unwinding.
+    struct Restore(IdStrategy);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_id_strategy(self.0);
+        }
+    }
+
+    let _restore = Restore(id_strategy());
+    set_id_strategy(strategy);
+    f()
+}
+
+/// The next node id according to the current thread's strategy.
+pub(crate) fn next_id() -> Uuid {
+    ID_GENERATOR.with(|generator| generator.borrow_mut().next_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    #[test]
+    fn sequential_ids() {
+        restart_id_sequence();
+        let ids: Vec<Uuid> = with_id_strategy(IdStrategy::Sequential, || {
+            (0..3)
+                .map(|value| TreeNode::new(value, None, None).get_id())
+                .collect()
+        });
+        assert_eq!(
+            ids,
+            vec![Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3)]
+        );
+        assert_eq!(id_strategy(), IdStrategy::default());
+    }
+
+    #[test]
+    fn ids_do_not_repeat() {
+        let outer = with_id_strategy(IdStrategy::Sequential, || {
+            let first = TreeNode::new(1, None, None).get_id();
+            let nested = with_id_strategy(IdStrategy::Sequential, || {
+                TreeNode::new(2, None, None).get_id()
+            });
+            (first, nested)
+        });
+        assert_ne!(outer.0, outer.1);
+
+        let before = TreeNode::new(3, None, None).get_id();
+        set_id_strategy(IdStrategy::Sequential);
+        assert_ne!(TreeNode::new(4, None, None).get_id(), before);
+        set_id_strategy(IdStrategy::default());
+    }
+
+    #[test]
+    fn strategy_restored_after_panic() {
+        let result = std::panic::catch_unwind(|| {
+            with_id_strategy(IdStrategy::Deterministic { seed: 1 }, || {
+                panic!("building failed")
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(id_strategy(), IdStrategy::default());
+    }
+
+    #[test]
+    fn deterministic_ids() {
+        let build = |seed| {
+            restart_id_sequence();
+            with_id_strategy(IdStrategy::Deterministic { seed }, || {
+                let leaf = TreeNode::new_rc(2, None, None);
+                let root = TreeNode::new_rc(1, Some(leaf.clone()), None);
+                let root_id = root.borrow().get_id();
+                let leaf_id = leaf.borrow().get_id();
+                (root_id, leaf_id)
+            })
+        };
+        assert_eq!(build(7), build(7));
+        assert_ne!(build(7), build(8));
+        assert_ne!(build(7).0, build(7).1);
+    }
+}
@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::rc::{Rc, Weak};
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// Entry of a tree's id index. Nodes are held weakly so the index never keeps a node alive after
+/// it has been removed from the tree.
+#[derive(Debug, Clone)]
+pub(crate) struct IndexEntry<T: Sized + Copy> {
+    pub(crate) node: Weak<RefCell<TreeNode<T>>>,
+    pub(crate) parent: Option<Uuid>,
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Creates a tree that maintains an id index from the start, see `Tree::build_index`.
+    pub fn new_indexed(root: TreeNodeRef<T>) -> Self {
+        let mut tree = Tree::new(root);
+        tree.build_index();
+        tree
+    }
+
+    /// Builds an index from node id to node and parent, so that `Tree::get_by_id`,
+    /// `Tree::parent_of` and `Tree::get_parent` take constant time and `Tree::depth_of` takes
+    /// O(depth).
+    ///
+    /// The index is kept up to date by the tree's own mutation methods such as
+    /// `Tree::insert_left`. Changes made directly through a node's `left`/`right` fields are not
+    /// seen by it, so rebuild the index after editing nodes by hand.
+    pub fn build_index(&mut self) {
+        let mut index: HashMap<Uuid, IndexEntry<T>> = HashMap::new();
+        for (node_ref, parent) in self.pre_order_with_parents() {
+            index.insert(
+                node_ref.borrow().id,
+                IndexEntry {
+                    node: Rc::downgrade(&node_ref),
+                    parent: parent.map(|parent| parent.borrow().id),
+                },
+            );
+        }
+        self.index = Some(index);
+    }
+
+    /// Stops maintaining the id index, lookups go back to searching the tree.
+    pub fn drop_index(&mut self) {
+        self.index = None;
+    }
+
+    /// Whether the tree maintains an id index.
+    pub fn has_index(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// The parent of the node with the given id. Returns `None` for the root and for ids that are
+    /// not in the tree.
+    pub fn parent_of(&self, id: Uuid) -> Option<TreeNodeRef<T>> {
+        match &self.index {
+            Some(index) => {
+                let parent = index.get(&id)?.parent?;
+                index.get(&parent)?.node.upgrade()
+            }
+            None => self
+                .pre_order_with_parents()
+                .into_iter()
+                .find(|(node_ref, _)| node_ref.borrow().id == id)
+                .and_then(|(_, parent)| parent),
+        }
+    }
+
+    /// Looks up a node in the index, `None` if the tree has no index or the node is not in it.
+    pub(crate) fn indexed_node(&self, id: Uuid) -> Option<Option<TreeNodeRef<T>>> {
+        let index = self.index.as_ref()?;
+        Some(index.get(&id).and_then(|entry| entry.node.upgrade()))
+    }
+
+    /// The id of the indexed parent of a node, `None` if the tree has no index.
+    pub(crate) fn indexed_parent(&self, id: Uuid) -> Option<Option<Uuid>> {
+        let index = self.index.as_ref()?;
+        Some(index.get(&id).and_then(|entry| entry.parent))
+    }
+
+    /// Adds `subtree` and all of its descendants to the index, if the tree has one.
+    pub(crate) fn index_subtree(&mut self, subtree: &TreeNodeRef<T>, parent: Option<Uuid>) {
+        let Some(index) = self.index.as_mut() else {
+            return;
+        };
+        let mut stack: Vec<(TreeNodeRef<T>, Option<Uuid>)> = vec![(subtree.clone(), parent)];
+        while let Some((current, parent)) = stack.pop() {
+            let node = current.borrow();
+            for child in [&node.left, &node.right].into_iter().flatten() {
+                stack.push((child.clone(), Some(node.id)));
+            }
+            index.insert(
+                node.id,
+                IndexEntry {
+                    node: Rc::downgrade(&current),
+                    parent,
+                },
+            );
+        }
+    }
+
+    /// Removes `subtree` and all of its descendants from the index, if the tree has one.
+    pub(crate) fn unindex_subtree(&mut self, subtree: &TreeNodeRef<T>) {
+        let Some(index) = self.index.as_mut() else {
+            return;
+        };
+        let mut stack: Vec<TreeNodeRef<T>> = vec![subtree.clone()];
+        while let Some(current) = stack.pop() {
+            let node = current.borrow();
+            stack.extend(node.left.clone());
+            stack.extend(node.right.clone());
+            index.remove(&node.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_lookups() {
+        // Test tree:
+        //                 1
+        //                / \
+        //               2   3
+        //              / \
+        //             4   5
+        //
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+
+        let mut tree = Tree::new_indexed(node1.clone());
+        assert!(tree.has_index());
+
+        let id5 = node5.borrow().get_id();
+        assert!(Rc::ptr_eq(&tree.get_by_id(id5).unwrap(), &node5));
+        assert!(Rc::ptr_eq(&tree.parent_of(id5).unwrap(), &node2));
+        assert!(Rc::ptr_eq(&tree.get_parent(&node4), &node2));
+        assert!(tree.parent_of(node1.borrow().get_id()).is_none());
+        assert_eq!(tree.depth_of(&node5), Some(2));
+
+        let id3 = node3.borrow().get_id();
+        let node6 = tree.insert_right(id3, 6).unwrap();
+        let id6 = node6.borrow().get_id();
+        assert!(Rc::ptr_eq(&tree.get_by_id(id6).unwrap(), &node6));
+        assert!(Rc::ptr_eq(&tree.parent_of(id6).unwrap(), &node3));
+        assert_eq!(tree.depth_of(&node6), Some(2));
+
+        tree.drop_index();
+        assert!(!tree.has_index());
+        assert!(Rc::ptr_eq(&tree.parent_of(id6).unwrap(), &node3));
+        assert!(Rc::ptr_eq(&tree.get_by_id(id5).unwrap(), &node5));
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt::{self, Debug, Display};
+
+use crate::avl::{self, AvlPayload};
+use crate::{Tree, TreeNodeRef};
+
+/// The payload of an `IntervalTree` node: the closed interval `start..=end`, its value and the
+/// augmentation kept up to date on every change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntervalEntry<K, V> {
+    pub start: K,
+    pub end: K,
+    pub value: V,
+    /// The largest `end` in the subtree of this node.
+    pub max_end: K,
+    height: usize,
+}
+
+/// Shows as `start..=end: value (max max_end)`.
+impl<K: Display, V: Display> Display for IntervalEntry<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}..={}: {} (max {})",
+            self.start, self.end, self.value, self.max_end
+        )
+    }
+}
+
+impl<K: Ord + Copy, V: Copy> AvlPayload for IntervalEntry<K, V> {
+    type Key = (K, K);
+
+    fn key(&self) -> (K, K) {
+        (self.start, self.end)
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn update(&mut self, left: Option<&Self>, right: Option<&Self>) {
+        self.height = 1;
+        self.max_end = self.end;
+        for child in [left, right].into_iter().flatten() {
+            self.height = self.height.max(child.height + 1);
+            self.max_end = self.max_end.max(child.max_end);
+        }
+    }
+}
+
+type Link<K, V> = TreeNodeRef<IntervalEntry<K, V>>;
+
+/// A set of closed intervals with values, answering which intervals overlap a point or a range.
+///
+/// The intervals are kept in an AVL tree of `TreeNode`s ordered by `(start, end)`, where every
+/// node also stores the largest end point below it. Queries use it to skip subtrees that end
+/// before the queried range. Equal intervals may be inserted more than once.
+pub struct IntervalTree<K: Copy, V: Copy> {
+    root: Option<Link<K, V>>,
+    len: usize,
+}
+
+impl<K: Ord + Copy, V: Copy> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Copy, V: Copy> IntervalTree<K, V> {
+    pub fn new() -> Self {
+        IntervalTree { root: None, len: 0 }
+    }
+
+    /// The number of intervals in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts the closed interval `start..=end`. Fails if `end` is before `start`.
+    pub fn insert(&mut self, start: K, end: K, value: V) -> Result<(), String> {
+        if end < start {
+            return Err("Attempted to insert an interval that ends before it starts".to_string());
+        }
+        let entry = IntervalEntry {
+            start,
+            end,
+            value,
+            max_end: end,
+            height: 1,
+        };
+        self.root = Some(avl::insert(self.root.take(), avl::leaf(entry)));
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes one interval `start..=end` and returns its value, or `None` if there is none.
+    pub fn remove(&mut self, start: K, end: K) -> Option<V> {
+        let (root, removed) = avl::remove(self.root.take(), (start, end));
+        self.root = root;
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed.map(|entry| entry.value)
+    }
+
+    /// The intervals that contain `point`, ordered by start.
+    pub fn stab(&self, point: K) -> Vec<(K, K, V)> {
+        self.overlapping(point, point)
+    }
+
+    /// The intervals that share at least one point with `start..=end`, ordered by start. Empty if
+    /// `end` is before `start`.
+    pub fn overlapping(&self, start: K, end: K) -> Vec<(K, K, V)> {
+        let mut found: Vec<(K, K, V)> = Vec::new();
+        if start <= end {
+            collect_overlapping(&self.root, start, end, &mut found);
+        }
+        found
+    }
+
+    /// All intervals, ordered by start.
+    pub fn intervals(&self) -> Vec<(K, K, V)> {
+        let mut found: Vec<(K, K, V)> = Vec::new();
+        let mut stack: Vec<Link<K, V>> = Vec::new();
+        let mut current = self.root.clone();
+        while current.is_some() || !stack.is_empty() {
+            while let Some(node_ref) = current {
+                current = node_ref.borrow().left.clone();
+                stack.push(node_ref);
+            }
+            let node_ref = stack.pop().expect("loop condition");
+            let node = node_ref.borrow();
+            found.push((node.value.start, node.value.end, node.value.value));
+            current = node.right.clone();
+        }
+        found
+    }
+}
+
+impl<K: Ord + Copy + Debug + Display, V: Copy + Debug + Display> IntervalTree<K, V> {
+    /// A copy of the tree for export with `Tree::save_typst` and friends, or `None` when there
+    /// are no intervals.
+    pub fn to_tree(&self) -> Option<Tree<IntervalEntry<K, V>>> {
+        self.root
+            .as_ref()
+            .map(|root| Tree::new(root.clone()).deep_clone())
+    }
+}
+
+fn collect_overlapping<K: Ord + Copy, V: Copy>(
+    link: &Option<Link<K, V>>,
+    start: K,
+    end: K,
+    found: &mut Vec<(K, K, V)>,
+) {
+    let Some(node_ref) = link else {
+        return;
+    };
+    let node = node_ref.borrow();
+    if node.value.max_end < start {
+        return;
+    }
+    collect_overlapping(&node.left, start, end, found);
+    if node.value.start <= end {
+        if start <= node.value.end {
+            found.push((node.value.start, node.value.end, node.value.value));
+        }
+        collect_overlapping(&node.right, start, end, found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avl::check;
+
+    #[test]
+    fn insert_and_query() {
+        let mut tree = IntervalTree::new();
+        for (start, end, task) in [(15, 20, 'a'), (10, 30, 'b'), (17, 19, 'c'), (5, 20, 'd')] {
+            tree.insert(start, end, task).unwrap();
+        }
+        tree.insert(12, 15, 'e').unwrap();
+        tree.insert(30, 40, 'f').unwrap();
+        check(&tree.root);
+        assert_eq!(tree.len(), 6);
+
+        assert_eq!(
+            tree.stab(16),
+            vec![(5, 20, 'd'), (10, 30, 'b'), (15, 20, 'a')]
+        );
+        assert_eq!(tree.stab(30), vec![(10, 30, 'b'), (30, 40, 'f')]);
+        assert!(tree.stab(41).is_empty());
+        assert_eq!(tree.overlapping(0, 11), vec![(5, 20, 'd'), (10, 30, 'b')]);
+        assert_eq!(tree.overlapping(21, 25), vec![(10, 30, 'b')]);
+        assert!(tree.overlapping(25, 21).is_empty());
+
+        assert_eq!(
+            tree.insert(3, 2, 'g').err(),
+            Some("Attempted to insert an interval that ends before it starts".to_string())
+        );
+
+        assert_eq!(
+            tree.to_tree().unwrap().typst_string(),
+            "([15..=20: a (max 40)], ([10..=30: b (max 30)], [5..=20: d (max 20)], \
+             [12..=15: e (max 15)]), ([17..=19: c (max 40)], [30..=40: f (max 40)]))"
+        );
+        assert!(IntervalTree::<i32, char>::new().to_tree().is_none());
+    }
+
+    #[test]
+    fn remove() {
+        let mut tree = IntervalTree::new();
+        for i in 0..200 {
+            tree.insert(i, i + (i * 7) % 13, i).unwrap();
+        }
+        tree.insert(50, 55, -1).unwrap();
+        assert_eq!(check(&tree.root), 9);
+
+        for i in (0..200).step_by(3) {
+            assert_eq!(tree.remove(i, i + (i * 7) % 13), Some(i));
+            check(&tree.root);
+        }
+        assert_eq!(tree.remove(0, 0), None);
+        assert_eq!(tree.len(), 134);
+
+        let expected: Vec<(i32, i32, i32)> = (0..200)
+            .filter(|i| i % 3 != 0)
+            .map(|i| (i, i + (i * 7) % 13, i))
+            .filter(|&(start, end, _)| start <= 100 && 100 <= end)
+            .collect();
+        assert_eq!(tree.stab(100), expected);
+
+        assert_eq!(tree.remove(50, 55), Some(-1));
+        let all = tree.intervals();
+        assert_eq!(all.len(), 133);
+        assert!(all.windows(2).all(|pair| pair[0] <= pair[1]));
+        while let Some(&(start, end, _)) = tree.intervals().first() {
+            tree.remove(start, end);
+            check(&tree.root);
+        }
+        assert!(tree.is_empty());
+    }
+}
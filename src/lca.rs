@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNodeRef};
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// The deepest node that has both the node with id `a` and the node with id `b` in its
+    /// subtree, where a node counts as being in its own subtree. Returns `None` if either id is not
+    /// in the tree.
+    ///
+    /// This walks the whole tree once per call, build an `LcaIndex` with `Tree::lca_index` when
+    /// answering many queries on the same tree.
+    pub fn lowest_common_ancestor(&self, a: Uuid, b: Uuid) -> Option<TreeNodeRef<T>> {
+        let mut parents: HashMap<Uuid, Option<TreeNodeRef<T>>> = HashMap::new();
+        let mut nodes: HashMap<Uuid, TreeNodeRef<T>> = HashMap::new();
+        for (node_ref, parent) in self.pre_order_with_parents() {
+            let id = node_ref.borrow().id;
+            parents.insert(id, parent);
+            nodes.insert(id, node_ref);
+        }
+        if !nodes.contains_key(&b) {
+            return None;
+        }
+
+        let mut ancestors_of_a: HashSet<Uuid> = HashSet::new();
+        let mut current = nodes.get(&a).cloned();
+        while let Some(node_ref) = current {
+            let id = node_ref.borrow().id;
+            ancestors_of_a.insert(id);
+            current = parents[&id].clone();
+        }
+
+        let mut current = nodes.get(&b).cloned();
+        while let Some(node_ref) = current {
+            let id = node_ref.borrow().id;
+            if ancestors_of_a.contains(&id) {
+                return Some(node_ref);
+            }
+            current = parents[&id].clone();
+        }
+        None
+    }
+
+    /// Preprocesses the tree for fast lowest common ancestor and distance queries. The index is a
+    /// snapshot, it has to be rebuilt after the tree changes.
+    pub fn lca_index(&self) -> LcaIndex<T> {
+        LcaIndex::new(self)
+    }
+}
+
+/// Answers lowest common ancestor queries in O(1) after O(n log n) preprocessing.
+///
+/// The tree is walked once to record its Euler tour, the sequence of nodes visited when walking
+/// down and back up every edge. The lowest common ancestor of two nodes is then the shallowest
+/// node of the tour between their first appearances, which a sparse table of range minimums finds
+/// in constant time.
+#[derive(Debug, Clone)]
+pub struct LcaIndex<T: Sized + Copy> {
+    nodes: Vec<TreeNodeRef<T>>,
+    depths: Vec<usize>,
+    /// Index into `nodes` of each entry of the Euler tour.
+    euler: Vec<usize>,
+    /// Position of each node's first appearance in the Euler tour.
+    first: HashMap<Uuid, usize>,
+    /// `sparse[k][i]` is the position of the shallowest tour entry in `euler[i..i + 2^k]`.
+    sparse: Vec<Vec<usize>>,
+}
+
+impl<T: Sized + Copy> LcaIndex<T> {
+    /// Builds the index for `tree` in O(n log n).
+    pub fn new(tree: &Tree<T>) -> Self {
+        let mut nodes: Vec<TreeNodeRef<T>> = Vec::new();
+        let mut depths: Vec<usize> = Vec::new();
+        let mut euler: Vec<usize> = Vec::new();
+        let mut first: HashMap<Uuid, usize> = HashMap::new();
+
+        first.insert(tree.root.borrow().id, 0);
+        nodes.push(tree.root.clone());
+        depths.push(0);
+        euler.push(0);
+
+        // Each frame is a node index and how many of its children have been walked so far.
+        let mut walk: Vec<(usize, usize)> = vec![(0, 0)];
+        while let Some((index, walked)) = walk.pop() {
+            let child = {
+                let node = nodes[index].borrow();
+                [node.left.clone(), node.right.clone()]
+                    .into_iter()
+                    .flatten()
+                    .nth(walked)
+            };
+            match child {
+                Some(child) => {
+                    walk.push((index, walked + 1));
+                    let child_index = nodes.len();
+                    first.insert(child.borrow().id, euler.len());
+                    depths.push(depths[index] + 1);
+                    nodes.push(child);
+                    euler.push(child_index);
+                    walk.push((child_index, 0));
+                }
+                None => {
+                    // Back up to the parent, which appears in the tour again.
+                    if let Some((parent, _)) = walk.last() {
+                        euler.push(*parent);
+                    }
+                }
+            }
+        }
+
+        let mut sparse: Vec<Vec<usize>> = vec![(0..euler.len()).collect()];
+        let mut span = 1;
+        while span * 2 <= euler.len() {
+            let previous = sparse.last().unwrap();
+            let level: Vec<usize> = (0..=euler.len() - span * 2)
+                .map(|i| {
+                    let (a, b) = (previous[i], previous[i + span]);
+                    if depths[euler[a]] <= depths[euler[b]] {
+                        a
+                    } else {
+                        b
+                    }
+                })
+                .collect();
+            sparse.push(level);
+            span *= 2;
+        }
+
+        LcaIndex {
+            nodes,
+            depths,
+            euler,
+            first,
+            sparse,
+        }
+    }
+
+    /// Index into `nodes` of the shallowest node of the tour between positions `from` and `to`.
+    fn shallowest(&self, from: usize, to: usize) -> usize {
+        let (from, to) = (from.min(to), from.max(to));
+        let level = (usize::BITS - 1 - (to - from + 1).leading_zeros()) as usize;
+        let a = self.sparse[level][from];
+        let b = self.sparse[level][to + 1 - (1 << level)];
+        if self.depths[self.euler[a]] <= self.depths[self.euler[b]] {
+            self.euler[a]
+        } else {
+            self.euler[b]
+        }
+    }
+
+    /// The lowest common ancestor of the nodes with ids `a` and `b`, or `None` if either id was
+    /// not in the tree when the index was built.
+    pub fn lca(&self, a: Uuid, b: Uuid) -> Option<TreeNodeRef<T>> {
+        let index = self.shallowest(*self.first.get(&a)?, *self.first.get(&b)?);
+        Some(self.nodes[index].clone())
+    }
+
+    /// The depth of the node with the given id, measured in edges from the root.
+    pub fn depth(&self, id: Uuid) -> Option<usize> {
+        let position = *self.first.get(&id)?;
+        Some(self.depths[self.euler[position]])
+    }
+
+    /// The number of edges on the path between the nodes with ids `a` and `b`.
+    pub fn distance(&self, a: Uuid, b: Uuid) -> Option<usize> {
+        let (first_a, first_b) = (*self.first.get(&a)?, *self.first.get(&b)?);
+        let common = self.depths[self.shallowest(first_a, first_b)];
+        Some(self.depths[self.euler[first_a]] + self.depths[self.euler[first_b]] - 2 * common)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> (Tree<i32>, Vec<Uuid>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8.clone()));
+        let node3 = TreeNode::new_rc(3, None, Some(node6.clone()));
+        let node5 = TreeNode::new_rc(5, Some(node7.clone()), None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+
+        let ids = [
+            &node1, &node2, &node3, &node4, &node5, &node6, &node7, &node8,
+        ]
+        .iter()
+        .map(|node| node.borrow().get_id())
+        .collect();
+        (Tree::new(node1), ids)
+    }
+
+    #[test]
+    fn lowest_common_ancestor() {
+        let (tree, ids) = test_tree();
+        let lca = |a: usize, b: usize| {
+            tree.lowest_common_ancestor(ids[a], ids[b])
+                .map(|node| node.borrow().value)
+        };
+        assert_eq!(lca(3, 6), Some(2));
+        assert_eq!(lca(6, 7), Some(1));
+        assert_eq!(lca(2, 7), Some(3));
+        assert_eq!(lca(4, 4), Some(5));
+        assert_eq!(lca(0, 5), Some(1));
+        assert_eq!(tree.lowest_common_ancestor(ids[0], Uuid::nil()), None);
+    }
+
+    #[test]
+    fn lca_index() {
+        let (tree, ids) = test_tree();
+        let index = tree.lca_index();
+        for &a in &ids {
+            for &b in &ids {
+                assert_eq!(
+                    index.lca(a, b).map(|node| node.borrow().get_id()),
+                    tree.lowest_common_ancestor(a, b)
+                        .map(|node| node.borrow().get_id())
+                );
+            }
+        }
+
+        assert_eq!(index.depth(ids[6]), Some(3));
+        assert_eq!(index.distance(ids[6], ids[7]), Some(6));
+        assert_eq!(index.distance(ids[3], ids[1]), Some(1));
+        assert_eq!(index.distance(ids[2], ids[2]), Some(0));
+        assert_eq!(index.distance(ids[2], Uuid::nil()), None);
+    }
+}
@@ -6,17 +6,96 @@ use std::fmt::Display;
 use std::fs::File;
 use std::io::Write;
 //use std::error::Error;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::{cell::RefCell, rc::Rc};
 
 use uuid::Uuid;
 
-mod preorderiter;
-//use crate::preorderiter::*;
-
-#[derive(Debug, Clone, PartialEq)]
+use crate::index::IndexEntry;
+use crate::visitor::{DepthVisitor, TypstVisitor};
+
+mod avl;
+mod compare;
+mod cursor;
+mod decision;
+mod deep_clone;
+mod diff;
+mod edit;
+mod expr;
+mod functional;
+mod huffman;
+mod id;
+mod index;
+mod interval;
+mod lca;
+mod metrics;
+mod morris;
+mod order;
+mod paths;
+mod segment;
+mod size;
+mod symbolic;
+mod terminal;
+mod threaded;
+mod validate;
+mod visitor;
+
+pub use cursor::TreeCursor;
+pub use decision::{DecisionNode, Outcome};
+pub use diff::{Edit, NodeKey, Slot};
+pub use edit::Side;
+pub use expr::{BinaryOp, ExprNode};
+pub use huffman::HuffmanNode;
+pub use id::{id_strategy, restart_id_sequence, set_id_strategy, with_id_strategy, IdStrategy};
+pub use interval::{IntervalEntry, IntervalTree};
+pub use lca::LcaIndex;
+pub use metrics::NodeAnnotation;
+pub use order::{OrderStatisticTree, RankedEntry};
+pub use segment::{Segment, SegmentTree};
+pub use threaded::ThreadedTree;
+pub use validate::ValidationReport;
+pub use visitor::{TreeVisitor, VisitFlow};
+
+/// A binary tree, held through a shared handle to its root node.
+///
+/// `Clone` copies the handle, not the nodes: a cloned tree shares every node with the original.
+/// Use `Tree::deep_clone` for a structural copy. Since an edit through one tree would leave the
+/// other's caches stale, the clone starts without annotations, id index or tracked sizes; call
+/// `Tree::build_index` or `Tree::track_sizes` on it to get them back.
+///
+/// Dropping a tree releases its nodes one at a time rather than recursively, so trees of any
+/// depth can be dropped without overflowing the stack. Nodes that are still held elsewhere, e.g.
+/// through a `TreeNodeRef` kept by the caller, stay alive together with their descendants.
+#[derive(Debug)]
 pub struct Tree<T: Sized + Copy> {
     pub root: TreeNodeRef<T>,
+    /// Cached per-node height/size/parent information, filled in by `Tree::annotate` and cleared
+    /// by `Tree::invalidate_annotations`.
+    pub(crate) annotations: RefCell<Option<HashMap<Uuid, NodeAnnotation>>>,
+    /// Optional index from node id to node and parent, see `Tree::build_index`.
+    pub(crate) index: Option<HashMap<Uuid, IndexEntry<T>>>,
+    /// Optional subtree size of every node, see `Tree::track_sizes`.
+    pub(crate) sizes: Option<HashMap<Uuid, usize>>,
+}
+
+impl<T: Sized + Copy> Clone for Tree<T> {
+    fn clone(&self) -> Self {
+        Tree {
+            root: self.root.clone(),
+            annotations: RefCell::new(None),
+            index: None,
+            sizes: None,
+        }
+    }
+}
+
+/// Two trees are equal when their nodes are equal, cached annotations and indexes are not
+/// compared.
+impl<T: Sized + Copy + PartialEq> PartialEq for Tree<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
 }
 
 //pub struct TypstConfig {
@@ -25,10 +104,20 @@ pub struct Tree<T: Sized + Copy> {
 
 impl<T: Sized + Copy + Debug + Display> Tree<T> {
     pub fn new(root: TreeNodeRef<T>) -> Self {
-        Tree { root }
+        Tree {
+            root,
+            annotations: RefCell::new(None),
+            index: None,
+            sizes: None,
+        }
     }
 
+    /// Find a node by its id. Takes constant time if the tree maintains an index (see
+    /// `Tree::build_index`), otherwise searches the tree.
     pub fn get_by_id(&self, id: Uuid) -> Option<TreeNodeRef<T>> {
+        if let Some(indexed) = self.indexed_node(id) {
+            return indexed;
+        }
         self.root.get_by_id(id)
     }
 
@@ -44,68 +133,36 @@ impl<T: Sized + Copy + Debug + Display> Tree<T> {
     //
     pub fn get_parent(&self, node_ref: &TreeNodeRef<T>) -> TreeNodeRef<T> {
         let node = node_ref.borrow();
-        // TODO: error handling
-        Tree::get_parent_rec(&self.root, node.id).expect("Node not found")
-    }
-
-    fn get_parent_rec(node_ref: &TreeNodeRef<T>, find_id: Uuid) -> Option<TreeNodeRef<T>> {
-        let node = node_ref.borrow();
-        let debug_value = node.value;
-        println!("debug_value: {}", debug_value);
-
-        if let Some(ref right) = node.right {
-            if right.borrow().id == find_id {
-                // Clones the Rc, not the value
-                return Some(node_ref.clone());
-            }
-            let right_opt = Tree::get_parent_rec(right, find_id);
-            if right_opt.is_some() {
-                return right_opt;
-            }
-        }
-        if let Some(ref left) = node.left {
-            if left.borrow().id == find_id {
-                // Clones the Rc, not the value
-                return Some(node_ref.clone());
-            }
-            let left_opt = Tree::get_parent_rec(left, find_id);
-            if left_opt.is_some() {
-                return left_opt;
-            }
+        if let Some(parent_id) = self.indexed_parent(node.id) {
+            let parent_id = parent_id.expect("Node not found");
+            return self.get_by_id(parent_id).expect("Node not found");
         }
-        None
+        // TODO: error handling
+        self.parent_of(node.id).expect("Node not found")
     }
 
+    /// The depth of the deepest node in the tree, the same as `Tree::height`.
     pub fn max_depth(&self) -> isize {
-        let root: &TreeNode<T> = &self.root.borrow();
-        let node_vec = root.pre_order_vec();
-
-        let mut depth: isize = TreeNode::depth(root, &node_vec[0].borrow().id);
-        for node in node_vec {
-            let node_depth = TreeNode::depth(root, &node.borrow().id);
-            if depth < node_depth {
-                depth = node_depth;
-            }
-        }
-        depth
+        self.height() as isize
     }
 
     /// The maximum possible width of the tree, given the depth. This is not the same as the max
     /// width. Where max width is the greatest number of nodes on a level, the max possible width
-    /// is the max width if all nodes up to the tree's depth were populated.
-    pub fn max_width_upper(&self) -> isize {
-        let depth: isize = self.max_depth();
-
-        // max width is properly 1 on a lone leaf.
-        if depth == 0 {
-            return 1;
-        }
-        1 + 2_isize.pow(depth.try_into().unwrap())
+    /// is the max width if all nodes up to the tree's depth were populated, i.e. `2^depth`.
+    /// See `Tree::max_width` for the actual width. Returns `None` when `2^depth` does not fit in an
+    /// `isize`, which happens from depth 63 on.
+    pub fn max_width_upper(&self) -> Option<isize> {
+        let depth = u32::try_from(self.max_depth()).ok()?;
+        2_isize.checked_pow(depth)
     }
 
     /// Takes the tree and formats it into a typst representation of a binary tree node structure.
     /// The format of the tree node was taken from:
     /// https://sitandr.github.io/typst-examples-book/book/packages/graphs.html
+    ///
+    /// Unlike the terminal renderer this does not go through `Tree::levels`: the format nests every
+    /// child inside its parent's array, so it is written by a depth-first walk, and the placement
+    /// of the levels is left to the Typst tree package.
     pub fn typst_string(&self) -> String {
         let mut out_string = String::new();
 
@@ -124,19 +181,54 @@ impl<T: Sized + Copy + Debug + Display> Tree<T> {
         // e.g.
         //[A], ([B], [C], [D]), ([E], [F])
 
-        let post_string = r#"
-)
+        let mut out_string = String::from(pre_string);
+
+        out_string.push_str(self.typst_string().as_str());
+        out_string.push_str("\n)\n");
+        out_string.push_str(TYPST_IMPORT);
+        out_string.push('#');
+        out_string.push_str(&typst_canvas("data"));
+
+        f.write_all(out_string.as_bytes())?;
+        Ok(())
+    }
 
+    /// A Typst document drawing this tree and `other` next to each other, e.g. an expression
+    /// before and after simplification.
+    pub fn typst_side_by_side(&self, other: &Tree<T>) -> String {
+        let mut out_string = String::new();
+        out_string.push_str(&format!("\n#let before = (\n{}\n)\n", self.typst_string()));
+        out_string.push_str(&format!("#let after = (\n{}\n)\n", other.typst_string()));
+        out_string.push_str(TYPST_IMPORT);
+        out_string.push_str("#grid(columns: 2, column-gutter: 1cm,\n");
+        out_string.push_str(&typst_canvas("before"));
+        out_string.push_str(",\n");
+        out_string.push_str(&typst_canvas("after"));
+        out_string.push_str(")\n");
+        out_string
+    }
+
+    pub fn save_typst_side_by_side(&self, other: &Tree<T>, file: &str) -> std::io::Result<()> {
+        let mut f = File::create(file)?;
+        f.write_all(self.typst_side_by_side(other).as_bytes())
+    }
+}
+
+const TYPST_IMPORT: &str = r#"
 #import "@preview/cetz:0.1.2": canvas, draw, tree
 
-#canvas(length: 1cm, {
+"#;
+
+/// The cetz canvas that draws the tree stored in the Typst variable `data`.
+fn typst_canvas(data: &str) -> String {
+    r#"canvas(length: 1cm, {
   import draw: *
 
   set-style(content: (padding: .2),
     fill: gray.lighten(70%),
     stroke: gray.lighten(70%))
 
-  tree.tree(data, spread: 2.5, grow: 1.5, draw-node: (node, _) => {
+  tree.tree(DATA, spread: 2.5, grow: 1.5, draw-node: (node, _) => {
     circle((), radius: .45, stroke: none)
     content((), node.content)
   }, draw-edge: (from, to, _) => {
@@ -144,18 +236,18 @@ impl<T: Sized + Copy + Debug + Display> Tree<T> {
          (a: to, number: .6, abs: true, b: from), mark: (end: ">"))
   }, name: "tree")
 })
-"#;
-        let mut out_string = String::from(pre_string);
-
-        out_string.push_str(self.typst_string().as_str());
-        out_string.push_str(post_string);
-
-        f.write_all(out_string.as_bytes())?;
-        Ok(())
-    }
+"#
+    .replace("DATA", data)
 }
 
-#[derive(Debug, Clone)]
+/// `Clone` copies the node's value and id but shares its children with the original, use
+/// `TreeNode::deep_clone` to copy the whole subtree.
+///
+/// `TreeNode` implements `Drop` so that deep trees can be dropped without overflowing the stack.
+/// As a consequence children can no longer be moved out of an owned node, neither by
+/// destructuring (`let TreeNode { left, .. } = node;`) nor by a plain move (`let left =
+/// node.left;`), both of which compiled before. Take them out with `node.left.take()` instead.
+#[derive(Clone)]
 pub struct TreeNode<T: Sized + Copy> {
     pub value: T,
     pub left: Option<TreeNodeRef<T>>,
@@ -172,44 +264,117 @@ pub struct TreeNode<T: Sized + Copy> {
 /// function)
 impl<T: Sized + Copy + PartialEq> PartialEq for TreeNode<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.value == other.value && self.left == other.left && self.right == other.right
+        // Compares pairs of nodes from an explicit stack, so deep trees don't overflow the call
+        // stack.
+        let mut stack: Vec<(TreeNodeRef<T>, TreeNodeRef<T>)> = Vec::new();
+        let check = |a: &TreeNode<T>, b: &TreeNode<T>, stack: &mut Vec<_>| {
+            if a.value != b.value {
+                return false;
+            }
+            for (a_child, b_child) in [(&a.left, &b.left), (&a.right, &b.right)] {
+                match (a_child, b_child) {
+                    (Some(a_child), Some(b_child)) => {
+                        if !Rc::ptr_eq(a_child, b_child) {
+                            stack.push((a_child.clone(), b_child.clone()));
+                        }
+                    }
+                    (None, None) => {}
+                    _ => return false,
+                }
+            }
+            true
+        };
+        if !check(self, other, &mut stack) {
+            return false;
+        }
+        while let Some((a, b)) = stack.pop() {
+            if !check(&a.borrow(), &b.borrow(), &mut stack) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Children are shown by id only, printing them in full would recurse once per level.
+impl<T: Sized + Copy + Debug> Debug for TreeNode<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let child_id =
+            |child: &Option<TreeNodeRef<T>>| child.as_ref().map(|child| child.borrow().id);
+        f.debug_struct("TreeNode")
+            .field("value", &self.value)
+            .field("left", &child_id(&self.left))
+            .field("right", &child_id(&self.right))
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// Releases the subtree one node at a time. The default drop would drop each child from inside
+/// its parent's drop, one nested call per level, and overflow the stack on deep trees.
+///
+/// Children are moved out of every node before the node itself is dropped, so each nested drop
+/// sees a node without children and returns at once. The pending stack holds at most one entry
+/// per level plus one sibling each, and for the degenerate chains that caused the trouble it
+/// never holds more than one.
+impl<T: Sized + Copy> Drop for TreeNode<T> {
+    fn drop(&mut self) {
+        if self.left.is_none() && self.right.is_none() {
+            return;
+        }
+        let mut stack: Vec<TreeNodeRef<T>> = Vec::new();
+        stack.extend(self.left.take());
+        stack.extend(self.right.take());
+        while let Some(node_ref) = stack.pop() {
+            // Nodes still held elsewhere are left alone, their last owner releases them.
+            if let Ok(cell) = Rc::try_unwrap(node_ref) {
+                let mut node = cell.into_inner();
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
     }
 }
 
-type TreeNodeRef<T: Sized + Copy> = Rc<RefCell<TreeNode<T>>>;
+type TreeNodeRef<T> = Rc<RefCell<TreeNode<T>>>;
 
 impl<T: Sized + Copy + Display> TreeNode<T> {
     pub fn get_id(&self) -> Uuid {
         self.id
     }
     pub fn format_typst(&self) -> String {
-        if self.is_leaf() {
-            return format!("[{}]", self.value);
-        }
-        let mut out = String::from("(");
-
-        out.push_str(format!("[{}], ", self.value).as_str());
-        if let Some(left) = self.left.clone() {
-            out.push_str(format!("{}, ", left.borrow().format_typst()).as_str());
-        }
-        if let Some(right) = self.right.clone() {
-            out.push_str(right.borrow().format_typst().as_str());
-        }
-        out.push(')');
+        self.format_typst_with(&|node| format!("[{}]", node.value))
+    }
 
-        out
+    /// Like `format_typst`, but each node's content block is produced by `label`, e.g. to style
+    /// some of the nodes differently.
+    pub fn format_typst_with(&self, label: &dyn Fn(&TreeNode<T>) -> String) -> String {
+        let mut visitor = TypstVisitor::new(label);
+        self.walk(&mut visitor);
+        visitor.out
     }
 
     pub fn cmp_id(&self, id: Uuid) -> bool {
         self.id == id
     }
 
+    /// Creates a node whose id is picked by the current thread's `IdStrategy`.
     pub fn new(value: T, left: Option<TreeNodeRef<T>>, right: Option<TreeNodeRef<T>>) -> Self {
+        TreeNode::new_with_id(value, left, right, id::next_id())
+    }
+
+    /// Creates a node with an explicitly chosen id.
+    pub fn new_with_id(
+        value: T,
+        left: Option<TreeNodeRef<T>>,
+        right: Option<TreeNodeRef<T>>,
+        id: Uuid,
+    ) -> Self {
         TreeNode {
             value,
             left,
             right,
-            id: Uuid::new_v4(),
+            id,
         }
     }
 
@@ -218,12 +383,7 @@ impl<T: Sized + Copy + Display> TreeNode<T> {
         left: Option<TreeNodeRef<T>>,
         right: Option<TreeNodeRef<T>>,
     ) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(TreeNode {
-            value,
-            left,
-            right,
-            id: Uuid::new_v4(),
-        }))
+        Rc::new(RefCell::new(TreeNode::new(value, left, right)))
     }
 
     pub fn add_left(&mut self, value: T) {
@@ -270,31 +430,32 @@ impl<T: Sized + Copy + Display> TreeNode<T> {
         count
     }
 
+    /// Counts the node and its descendants. Consumes the node, see `TreeNode::size` for a version
+    /// that does not.
     pub fn count(self) -> usize {
-        let mut node_total: usize = 0;
-        let start_node: Rc<RefCell<TreeNode<T>>> = Rc::new(RefCell::new(self));
-        let mut stack: Vec<TreeNodeRef<T>> = vec![start_node];
+        self.size()
+    }
 
-        //while !stack.is_empty() {
+    /// The number of nodes in the subtree of this node, itself included.
+    pub fn size(&self) -> usize {
+        let mut size = 1;
+        let mut stack: Vec<TreeNodeRef<T>> = Vec::new();
+        stack.extend(self.left.clone());
+        stack.extend(self.right.clone());
         while let Some(current) = stack.pop() {
-            node_total += 1;
-
-            //let current: Rc<RefCell<TreeNode<T>>> = stack.pop().unwrap();
-            if let Some(left) = &current.borrow().left {
-                stack.push(left.to_owned());
-            };
-            if let Some(right) = &current.borrow().right {
-                stack.push(right.to_owned());
-            };
+            size += 1;
+            let node = current.borrow();
+            stack.extend(node.left.clone());
+            stack.extend(node.right.clone());
         }
-        node_total
+        size
     }
 
     pub fn pre_order_vec(&self) -> Vec<TreeNodeRef<T>> {
-        // Not sure about possible repurcussions from the clone below, if both contain Rc's to the
-        // same nodes.
-        // TODO: Add some tests for what happens if the nodes are changed through the vector and/or
-        // through the original tree.
+        // The clone below is shallow: the first entry is a new node holding a copy of `self`,
+        // but its children are the same `Rc`s as in the original tree, so changes made to them
+        // through the vector show up in the tree as well. Use `deep_clone` for an independent
+        // copy.
         let start_node: TreeNodeRef<T> = Rc::new(RefCell::new(self.clone()));
         let mut traverse_stack: Vec<TreeNodeRef<T>> = vec![start_node.clone()];
         let mut pre_order_vec: Vec<TreeNodeRef<T>> = vec![start_node];
@@ -324,29 +485,14 @@ impl<T: Sized + Copy + Display> TreeNode<T> {
     ///                /     \
     /// depth: 3      8       7
     ///
-    // Algorithm taken from C++ implementation at:
-    // https://www.geeksforgeeks.org/height-and-depth-of-a-node-in-a-binary-tree/
+    // Walks the tree with a `DepthVisitor`, which stops as soon as the id is found.
     pub fn depth(root: &TreeNode<T>, _id: &Uuid) -> isize {
-        let mut dist: isize = -1;
-
-        // Check if current node is target node;
-        if &root.id == _id {
-            return dist + 1;
-        }
-        if let Some(left) = &root.left {
-            dist = TreeNode::depth(&left.borrow(), _id);
-            if dist >= 0 {
-                return dist + 1;
-            }
-        }
-        if let Some(right) = &root.right {
-            dist = TreeNode::depth(&right.borrow(), _id);
-            if dist >= 0 {
-                return dist + 1;
-            }
-        }
-
-        dist
+        let mut visitor = DepthVisitor {
+            id: *_id,
+            depth: None,
+        };
+        root.walk(&mut visitor);
+        visitor.depth.map_or(-1, |depth| depth as isize)
     }
 }
 
@@ -354,6 +500,12 @@ pub trait TreeNodeProperties<T: Copy + Sized> {
     fn get_by_id(&self, id: Uuid) -> Option<TreeNodeRef<T>>;
 }
 
+/// Subtree size of a shared node, so it can be asked without borrowing the node first.
+pub trait SubtreeSize {
+    /// The number of nodes in the subtree, see `TreeNode::size`.
+    fn size(&self) -> usize;
+}
+
 //impl<T: Copy + Sized + Display> TreeNodeProperties<T> for TreeNodeRef<T> {
 //    fn get_by_id(&self, id: Uuid) -> Option<TreeNodeRef<T>> {
 //        if self.borrow().id == id {
@@ -379,27 +531,27 @@ pub trait TreeNodeProperties<T: Copy + Sized> {
 /// Search children for node by uuid.
 impl<T: Copy + Sized + Display> TreeNodeProperties<T> for TreeNodeRef<T> {
     fn get_by_id(&self, id: Uuid) -> Option<TreeNodeRef<T>> {
-        if self.borrow().id == id {
-            return Some(self.clone());
-        }
-
-        if let Some(ref right) = self.borrow().right {
-            let right_ret = right.get_by_id(id);
-            if right_ret.is_some() {
-                return right_ret;
-            }
-        }
-        if let Some(ref left) = self.borrow().left {
-            let left_ret = left.get_by_id(id);
-            if left_ret.is_some() {
-                return left_ret;
+        // Right subtrees are searched before left ones, as in the earlier recursive version.
+        let mut stack: Vec<TreeNodeRef<T>> = vec![self.clone()];
+        while let Some(current) = stack.pop() {
+            let node = current.borrow();
+            if node.id == id {
+                drop(node);
+                return Some(current);
             }
+            stack.extend(node.left.clone());
+            stack.extend(node.right.clone());
         }
-
         None
     }
 }
 
+impl<T: Copy + Sized + Display> SubtreeSize for TreeNodeRef<T> {
+    fn size(&self) -> usize {
+        self.borrow().size()
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -499,7 +651,7 @@ mod tests {
         //                /     \
         //               7       8
         //
-        // max possible width: 1 + 2^3 = 9
+        // max possible width: 2^3 = 8
         let node4 = TreeNode::new_rc(4, None, None);
         let node7 = TreeNode::new_rc(7, None, None);
         let node8 = TreeNode::new_rc(7, None, None);
@@ -515,16 +667,25 @@ mod tests {
         let node1_rc = Rc::new(RefCell::new(node1));
 
         let tree = Tree::new(node1_rc);
-        assert_eq!(tree.max_width_upper(), 9);
+        assert_eq!(tree.max_width_upper(), Some(8));
 
         let tree = Tree::new(node2);
-        assert_eq!(tree.max_width_upper(), 5);
+        assert_eq!(tree.max_width_upper(), Some(4));
 
         let tree = Tree::new(node5);
-        assert_eq!(tree.max_width_upper(), 3);
+        assert_eq!(tree.max_width_upper(), Some(2));
 
         let tree = Tree::new(node7);
-        assert_eq!(tree.max_width_upper(), 1);
+        assert_eq!(tree.max_width_upper(), Some(1));
+
+        let mut node = TreeNode::new_rc(0, None, None);
+        for value in 1..=63 {
+            node = TreeNode::new_rc(value, Some(node), None);
+        }
+        let tree = Tree::new(node);
+        assert_eq!(tree.max_width_upper(), None);
+        let left = tree.root.borrow().left.clone().unwrap();
+        assert_eq!(Tree::new(left).max_width_upper(), Some(1 << 62));
     }
 
     #[test]
@@ -581,7 +742,7 @@ mod tests {
 
         let node1_rc = Rc::new(RefCell::new(node1));
         let tree1 = Tree::new(node1_rc);
-        tree1.save_typst("./typst_test.typ");
+        tree1.save_typst("./typst_test.typ").unwrap();
 
         let mut file = File::open("./typst_test.typ").unwrap();
         let mut contents = String::new();
@@ -685,4 +846,23 @@ mod tests {
         assert_eq!(node1_rc, tree.get_by_id(node1.id).unwrap());
         assert_eq!(node5, tree.get_by_id(node5.borrow().id).unwrap());
     }
+
+    #[test]
+    fn clone_drops_caches() {
+        let node2 = TreeNode::new_rc(2, None, None);
+        let mut tree = Tree::new_indexed(TreeNode::new_rc(1, Some(node2.clone()), None));
+        tree.track_sizes();
+        tree.annotate();
+
+        let mut clone = tree.clone();
+        assert!(!clone.has_index());
+        assert!(!clone.tracks_sizes());
+        assert!(!clone.is_annotated());
+        assert!(Rc::ptr_eq(&clone.root, &tree.root));
+
+        // An edit through the clone still shows up in the original.
+        let id2 = node2.borrow().id;
+        clone.insert_left(id2, 3).unwrap();
+        assert_eq!(tree.typst_string(), "([1], ([2], [3], ), )");
+    }
 }
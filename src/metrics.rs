@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// Cached information about a single node, computed by `Tree::annotate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeAnnotation {
+    /// Number of edges on the longest path from the node down to a leaf.
+    pub height: usize,
+    /// Number of nodes in the subtree rooted at the node, including the node itself.
+    pub size: usize,
+    /// Id of the node's parent, `None` for the root.
+    pub parent: Option<Uuid>,
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// The number of edges on the longest path from the root to a leaf. A lone root has height 0.
+    ///
+    /// Uses the cached annotations if `Tree::annotate` has been called, otherwise walks the tree
+    /// once level by level.
+    pub fn height(&self) -> usize {
+        let root_id = self.root.borrow().id;
+        if let Some(annotation) = self.annotation(root_id) {
+            return annotation.height;
+        }
+        self.root.borrow().height()
+    }
+
+    /// Computes the height, size and parent of every node in a single pass and caches the result
+    /// on the tree. The tree's own mutation methods, such as `Tree::insert_left`, clear the cache.
+    /// Changes made directly through a node's `left`/`right` fields are not tracked, so call
+    /// `Tree::invalidate_annotations` after editing nodes by hand.
+    pub fn annotate(&self) {
+        let order = self.pre_order_with_parents();
+
+        let mut annotations: HashMap<Uuid, NodeAnnotation> = HashMap::with_capacity(order.len());
+        for (node_ref, parent) in order.iter().rev() {
+            let node = node_ref.borrow();
+            let mut height = 0;
+            let mut size = 1;
+            for child in [&node.left, &node.right].into_iter().flatten() {
+                if let Some(child_annotation) = annotations.get(&child.borrow().id) {
+                    height = height.max(child_annotation.height + 1);
+                    size += child_annotation.size;
+                }
+            }
+            annotations.insert(
+                node.id,
+                NodeAnnotation {
+                    height,
+                    size,
+                    parent: parent.as_ref().map(|parent| parent.borrow().id),
+                },
+            );
+        }
+
+        *self.annotations.borrow_mut() = Some(annotations);
+    }
+
+    /// Every node of the tree in pre-order, paired with its parent. Children always come after
+    /// their parent, so walking the list backwards sees every child before its parent, which is
+    /// how the bottom-up computations in this crate avoid recursion.
+    pub(crate) fn pre_order_with_parents(&self) -> Vec<(TreeNodeRef<T>, Option<TreeNodeRef<T>>)> {
+        let mut order: Vec<(TreeNodeRef<T>, Option<TreeNodeRef<T>>)> = Vec::new();
+        let mut stack: Vec<(TreeNodeRef<T>, Option<TreeNodeRef<T>>)> =
+            vec![(self.root.clone(), None)];
+        while let Some((current, parent)) = stack.pop() {
+            {
+                let node = current.borrow();
+                if let Some(right) = &node.right {
+                    stack.push((right.clone(), Some(current.clone())));
+                }
+                if let Some(left) = &node.left {
+                    stack.push((left.clone(), Some(current.clone())));
+                }
+            }
+            order.push((current, parent));
+        }
+        order
+    }
+
+    /// Whether the tree currently holds cached annotations.
+    pub fn is_annotated(&self) -> bool {
+        self.annotations.borrow().is_some()
+    }
+
+    /// Drops the cached annotations, if any.
+    pub fn invalidate_annotations(&self) {
+        *self.annotations.borrow_mut() = None;
+    }
+
+    /// The cached annotation of the node with the given id. Returns `None` if the tree has not
+    /// been annotated or the id is not in the tree.
+    pub fn annotation(&self, id: Uuid) -> Option<NodeAnnotation> {
+        self.annotations
+            .borrow()
+            .as_ref()
+            .and_then(|annotations| annotations.get(&id).copied())
+    }
+
+    /// The height of the subtree rooted at the node with the given id.
+    pub fn height_of(&self, id: Uuid) -> Option<usize> {
+        if let Some(annotation) = self.annotation(id) {
+            return Some(annotation.height);
+        }
+        self.get_by_id(id).map(|node| node.borrow().height())
+    }
+
+    /// The depth of a node, measured in edges from the root.
+    ///
+    /// With an id index or cached annotations this walks up through the parents, taking O(depth).
+    /// Otherwise it is a single breadth-first search from the root. Returns `None` if the node is
+    /// not in the tree.
+    pub fn depth_of(&self, node_ref: &TreeNodeRef<T>) -> Option<usize> {
+        let find_id = node_ref.borrow().id;
+
+        if let Some(index) = &self.index {
+            let mut entry = index.get(&find_id)?;
+            let mut depth = 0;
+            while let Some(parent) = entry.parent {
+                depth += 1;
+                entry = index.get(&parent)?;
+            }
+            return Some(depth);
+        }
+
+        if let Some(annotations) = self.annotations.borrow().as_ref() {
+            let mut annotation = annotations.get(&find_id)?;
+            let mut depth = 0;
+            while let Some(parent) = annotation.parent {
+                depth += 1;
+                annotation = annotations.get(&parent)?;
+            }
+            return Some(depth);
+        }
+
+        let mut level: Vec<TreeNodeRef<T>> = vec![self.root.clone()];
+        let mut depth = 0;
+        while !level.is_empty() {
+            let mut next_level: Vec<TreeNodeRef<T>> = Vec::new();
+            for current in level {
+                let node = current.borrow();
+                if node.id == find_id {
+                    return Some(depth);
+                }
+                next_level.extend(node.left.clone());
+                next_level.extend(node.right.clone());
+            }
+            level = next_level;
+            depth += 1;
+        }
+        None
+    }
+
+    /// The nodes of the tree grouped by level, from the root down, each level ordered left to
+    /// right.
+    pub fn levels(&self) -> Vec<Vec<TreeNodeRef<T>>> {
+        self.positioned_levels()
+            .into_iter()
+            .map(|level| level.into_iter().map(|(node, _)| node).collect())
+            .collect()
+    }
+
+    /// The number of nodes on each level, from the root down.
+    pub fn level_widths(&self) -> Vec<usize> {
+        self.levels().iter().map(|level| level.len()).collect()
+    }
+
+    /// The greatest number of nodes on any single level.
+    pub fn max_width(&self) -> usize {
+        self.level_widths().into_iter().max().unwrap_or(0)
+    }
+
+    /// The width of the widest level when the missing nodes between the leftmost and rightmost
+    /// node of a level are counted as well, as if the level were part of a complete tree:
+    ///
+    /// ```text
+    ///        1
+    ///       / \
+    ///      2   3
+    ///     /     \
+    ///    4       5     <- width 4, counting the two gaps between 4 and 5
+    /// ```
+    ///
+    /// Positions are kept relative to the leftmost node of each level and use wrapping arithmetic,
+    /// so the result is exact as long as it fits in a `usize`.
+    pub fn max_width_with_gaps(&self) -> usize {
+        let mut level: Vec<(TreeNodeRef<T>, usize)> = vec![(self.root.clone(), 0)];
+        let mut max_width = 0;
+        while let (Some((_, first)), Some((_, last))) = (level.first(), level.last()) {
+            let first = *first;
+            max_width = max_width.max(last.wrapping_sub(first).wrapping_add(1));
+
+            let mut next_level: Vec<(TreeNodeRef<T>, usize)> = Vec::new();
+            for (current, position) in &level {
+                let node = current.borrow();
+                let position = position.wrapping_sub(first).wrapping_mul(2);
+                if let Some(left) = &node.left {
+                    next_level.push((left.clone(), position));
+                }
+                if let Some(right) = &node.right {
+                    next_level.push((right.clone(), position.wrapping_add(1)));
+                }
+            }
+            level = next_level;
+        }
+        max_width
+    }
+
+    /// Like `Tree::levels`, but also gives the horizontal centre of each node as a fraction of the
+    /// total width, with each level split into `2^depth` equal slots.
+    pub(crate) fn positioned_levels(&self) -> Vec<Vec<(TreeNodeRef<T>, f64)>> {
+        let mut levels: Vec<Vec<(TreeNodeRef<T>, f64)>> = Vec::new();
+        let mut level: Vec<(TreeNodeRef<T>, f64)> = vec![(self.root.clone(), 0.5)];
+        let mut half_slot = 0.25;
+        while !level.is_empty() {
+            let mut next_level: Vec<(TreeNodeRef<T>, f64)> = Vec::new();
+            for (current, centre) in &level {
+                let node = current.borrow();
+                if let Some(left) = &node.left {
+                    next_level.push((left.clone(), centre - half_slot));
+                }
+                if let Some(right) = &node.right {
+                    next_level.push((right.clone(), centre + half_slot));
+                }
+            }
+            levels.push(level);
+            level = next_level;
+            half_slot /= 2.0;
+        }
+        levels
+    }
+}
+
+impl<T: Sized + Copy> TreeNode<T> {
+    /// The number of edges on the longest path from this node down to a leaf, found by walking
+    /// the subtree level by level.
+    pub fn height(&self) -> usize {
+        let mut level: Vec<TreeNodeRef<T>> =
+            self.left.iter().chain(self.right.iter()).cloned().collect();
+        let mut height = 0;
+        while !level.is_empty() {
+            height += 1;
+            let mut next_level: Vec<TreeNodeRef<T>> = Vec::new();
+            for current in level {
+                let node = current.borrow();
+                next_level.extend(node.left.clone());
+                next_level.extend(node.right.clone());
+            }
+            level = next_level;
+        }
+        height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> (Tree<i32>, Vec<TreeNodeRef<i32>>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8.clone()));
+        let node3 = TreeNode::new_rc(3, None, Some(node6.clone()));
+        let node5 = TreeNode::new_rc(5, Some(node7.clone()), None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+
+        let tree = Tree::new(node1.clone());
+        (
+            tree,
+            vec![node1, node2, node3, node4, node5, node6, node7, node8],
+        )
+    }
+
+    #[test]
+    fn height() {
+        let (tree, nodes) = test_tree();
+        assert_eq!(tree.height(), 3);
+        assert_eq!(nodes[1].borrow().height(), 2);
+        assert_eq!(nodes[6].borrow().height(), 0);
+        assert_eq!(tree.height_of(nodes[2].borrow().get_id()), Some(2));
+    }
+
+    #[test]
+    fn annotate() {
+        let (tree, nodes) = test_tree();
+        assert!(!tree.is_annotated());
+        tree.annotate();
+        assert!(tree.is_annotated());
+
+        let root = tree.annotation(nodes[0].borrow().get_id()).unwrap();
+        assert_eq!(root.height, 3);
+        assert_eq!(root.size, 8);
+        assert_eq!(root.parent, None);
+
+        let node5 = tree.annotation(nodes[4].borrow().get_id()).unwrap();
+        assert_eq!(node5.height, 1);
+        assert_eq!(node5.size, 2);
+        assert_eq!(node5.parent, Some(nodes[1].borrow().get_id()));
+
+        tree.invalidate_annotations();
+        assert_eq!(tree.annotation(nodes[0].borrow().get_id()), None);
+    }
+
+    #[test]
+    fn level_widths() {
+        let (tree, nodes) = test_tree();
+        assert_eq!(tree.level_widths(), vec![1, 2, 3, 2]);
+        assert_eq!(tree.max_width(), 3);
+        // 7 is the left child of 5 and 8 the right child of 6: positions 2 and 7 of the
+        // bottom level.
+        assert_eq!(tree.max_width_with_gaps(), 6);
+
+        let values: Vec<Vec<i32>> = tree
+            .levels()
+            .iter()
+            .map(|level| level.iter().map(|node| node.borrow().value).collect())
+            .collect();
+        assert_eq!(values, vec![vec![1], vec![2, 3], vec![4, 5, 6], vec![7, 8]]);
+
+        let leaf = Tree::new(nodes[6].clone());
+        assert_eq!(leaf.level_widths(), vec![1]);
+        assert_eq!(leaf.max_width_with_gaps(), 1);
+    }
+
+    #[test]
+    fn depth_of() {
+        let (tree, nodes) = test_tree();
+        let expected = [0, 1, 1, 2, 2, 2, 3, 3];
+        for (node, depth) in nodes.iter().zip(expected) {
+            assert_eq!(tree.depth_of(node), Some(depth));
+        }
+
+        tree.annotate();
+        for (node, depth) in nodes.iter().zip(expected) {
+            assert_eq!(tree.depth_of(node), Some(depth));
+        }
+
+        let stranger = TreeNode::new_rc(9, None, None);
+        assert_eq!(tree.depth_of(&stranger), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use crate::{Tree, TreeNodeRef};
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Calls `f` on every value in in-order using Morris traversal, which needs no stack and only
+    /// a constant amount of extra memory.
+    ///
+    /// While the walk runs, the empty `right` slot of each node's in-order predecessor is
+    /// temporarily pointed back at the node, and restored once the node has been reached a second
+    /// time, so the tree is unchanged when the walk returns. If `f` panics, some of these threads
+    /// are left behind as reference cycles.
+    pub fn morris_in_order(&self, mut f: impl FnMut(&T)) {
+        self.morris(|value, first_visit, has_left| {
+            if !first_visit || !has_left {
+                f(value);
+            }
+        });
+    }
+
+    /// Like `Tree::morris_in_order`, but in pre-order.
+    pub fn morris_pre_order(&self, mut f: impl FnMut(&T)) {
+        self.morris(|value, first_visit, _| {
+            if first_visit {
+                f(value);
+            }
+        });
+    }
+
+    /// The Morris walk shared by both orders. Nodes with a left child are reached twice, before
+    /// and after their left subtree, leaves of the left spine once. `visit` gets the value,
+    /// whether this is the first time the node is reached and whether it has a left child.
+    fn morris(&self, mut visit: impl FnMut(&T, bool, bool)) {
+        let mut current: Option<TreeNodeRef<T>> = Some(self.root.clone());
+        while let Some(node_ref) = current {
+            let left = node_ref.borrow().left.clone();
+            let Some(left) = left else {
+                visit(&node_ref.borrow().value, true, false);
+                current = node_ref.borrow().right.clone();
+                continue;
+            };
+
+            // The rightmost node of the left subtree, stopping at a thread back to this node.
+            let mut predecessor = left.clone();
+            loop {
+                let next = predecessor.borrow().right.clone();
+                match next {
+                    Some(next) if !Rc::ptr_eq(&next, &node_ref) => predecessor = next,
+                    _ => break,
+                }
+            }
+
+            let mut predecessor = predecessor.borrow_mut();
+            if predecessor.right.is_none() {
+                predecessor.right = Some(node_ref.clone());
+                drop(predecessor);
+                visit(&node_ref.borrow().value, true, true);
+                current = Some(left);
+            } else {
+                predecessor.right = None;
+                drop(predecessor);
+                visit(&node_ref.borrow().value, false, true);
+                current = node_ref.borrow().right.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> Tree<i32> {
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, Some(node7), None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8));
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node3 = TreeNode::new_rc(3, None, Some(node6));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    #[test]
+    fn morris_orders() {
+        let tree = test_tree();
+        let copy = tree.deep_clone();
+
+        let mut in_order = Vec::new();
+        tree.morris_in_order(|value| in_order.push(*value));
+        assert_eq!(in_order, vec![4, 2, 7, 5, 1, 3, 6, 8]);
+
+        let mut pre_order = Vec::new();
+        tree.morris_pre_order(|value| pre_order.push(*value));
+        assert_eq!(pre_order, vec![1, 2, 4, 5, 7, 3, 6, 8]);
+
+        // All threads are removed again.
+        assert!(tree.is_identical(&copy));
+        assert!(tree.validate().is_valid());
+    }
+}
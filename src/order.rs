@@ -0,0 +1,233 @@
+use std::fmt::{self, Debug, Display};
+use std::ops::{Bound, RangeBounds};
+
+use crate::avl::{self, AvlPayload};
+use crate::{Tree, TreeNodeRef};
+
+/// The payload of an `OrderStatisticTree` node: a value and the size of its subtree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankedEntry<T> {
+    pub value: T,
+    /// The number of values in the subtree of this node, itself included.
+    pub size: usize,
+    height: usize,
+}
+
+/// Shows as `value (size)`.
+impl<T: Display> Display for RankedEntry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.value, self.size)
+    }
+}
+
+impl<T: Ord + Copy> AvlPayload for RankedEntry<T> {
+    type Key = T;
+
+    fn key(&self) -> T {
+        self.value
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn update(&mut self, left: Option<&Self>, right: Option<&Self>) {
+        self.height = 1;
+        self.size = 1;
+        for child in [left, right].into_iter().flatten() {
+            self.height = self.height.max(child.height + 1);
+            self.size += child.size;
+        }
+    }
+}
+
+type Link<T> = TreeNodeRef<RankedEntry<T>>;
+
+/// A sorted multiset answering "what is the k-th smallest value" and "how many values are
+/// smaller than this one" in O(log n).
+///
+/// The values are kept in the same kind of balanced tree as `IntervalTree`, with every node
+/// storing the size of its subtree, so the queries only walk one path down from the root.
+pub struct OrderStatisticTree<T: Copy> {
+    root: Option<Link<T>>,
+}
+
+impl<T: Ord + Copy> Default for OrderStatisticTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Copy> OrderStatisticTree<T> {
+    pub fn new() -> Self {
+        OrderStatisticTree { root: None }
+    }
+
+    /// The number of values in the tree.
+    pub fn len(&self) -> usize {
+        avl::payload(&self.root).map_or(0, |entry| entry.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Adds `value`, also if it is in the tree already.
+    pub fn insert(&mut self, value: T) {
+        let entry = RankedEntry {
+            value,
+            size: 1,
+            height: 1,
+        };
+        self.root = Some(avl::insert(self.root.take(), avl::leaf(entry)));
+    }
+
+    /// Removes one copy of `value`, returning whether there was one.
+    pub fn remove(&mut self, value: T) -> bool {
+        let (root, removed) = avl::remove(self.root.take(), value);
+        self.root = root;
+        removed.is_some()
+    }
+
+    /// The `k`-th smallest value, counting from 0, or `None` if there are no more than `k`
+    /// values.
+    pub fn select(&self, mut k: usize) -> Option<T> {
+        let mut current = self.root.clone();
+        while let Some(node_ref) = current {
+            let node = node_ref.borrow();
+            let left_size = avl::payload(&node.left).map_or(0, |entry| entry.size);
+            if k < left_size {
+                current = node.left.clone();
+            } else if k == left_size {
+                return Some(node.value.value);
+            } else {
+                k -= left_size + 1;
+                current = node.right.clone();
+            }
+        }
+        None
+    }
+
+    /// The number of values smaller than `value`, which is the position `value` has or would
+    /// have in sorted order.
+    pub fn rank(&self, value: T) -> usize {
+        self.count_below(value, false)
+    }
+
+    /// The number of values in `range`, e.g. `tree.count_range(3..=7)`.
+    pub fn count_range(&self, range: impl RangeBounds<T>) -> usize {
+        let below_start = match range.start_bound() {
+            Bound::Included(&start) => self.count_below(start, false),
+            Bound::Excluded(&start) => self.count_below(start, true),
+            Bound::Unbounded => 0,
+        };
+        let up_to_end = match range.end_bound() {
+            Bound::Included(&end) => self.count_below(end, true),
+            Bound::Excluded(&end) => self.count_below(end, false),
+            Bound::Unbounded => self.len(),
+        };
+        up_to_end.saturating_sub(below_start)
+    }
+
+    /// The number of values smaller than `value`, or no larger than it when `inclusive` is set.
+    fn count_below(&self, value: T, inclusive: bool) -> usize {
+        let mut count = 0;
+        let mut current = self.root.clone();
+        while let Some(node_ref) = current {
+            let node = node_ref.borrow();
+            let below = if inclusive {
+                node.value.value <= value
+            } else {
+                node.value.value < value
+            };
+            if below {
+                count += avl::payload(&node.left).map_or(0, |entry| entry.size) + 1;
+                current = node.right.clone();
+            } else {
+                current = node.left.clone();
+            }
+        }
+        count
+    }
+}
+
+impl<T: Ord + Copy + Debug + Display> OrderStatisticTree<T> {
+    /// A copy of the tree for export with `Tree::save_typst` and friends, or `None` when it is
+    /// empty.
+    pub fn to_tree(&self) -> Option<Tree<RankedEntry<T>>> {
+        self.root
+            .as_ref()
+            .map(|root| Tree::new(root.clone()).deep_clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avl::check;
+
+    #[test]
+    fn select_and_rank() {
+        let mut tree = OrderStatisticTree::new();
+        let values = [50, 20, 80, 10, 30, 70, 90, 30, 60];
+        for value in values {
+            tree.insert(value);
+        }
+        check(&tree.root);
+        assert_eq!(tree.len(), 9);
+
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        for (k, &value) in sorted.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(value));
+        }
+        assert_eq!(tree.select(9), None);
+
+        assert_eq!(tree.rank(10), 0);
+        assert_eq!(tree.rank(30), 2);
+        assert_eq!(tree.rank(35), 4);
+        assert_eq!(tree.rank(100), 9);
+
+        assert_eq!(tree.count_range(30..=70), 5);
+        assert_eq!(tree.count_range(30..70), 4);
+        assert_eq!(tree.count_range(..30), 2);
+        assert_eq!(tree.count_range(31..), 5);
+        assert_eq!(tree.count_range(..), 9);
+        assert_eq!(
+            tree.count_range((Bound::Included(70), Bound::Excluded(30))),
+            0
+        );
+
+        assert_eq!(
+            tree.to_tree().unwrap().typst_string(),
+            "([50 (9)], ([20 (4)], [10 (1)], ([30 (2)], [30 (1)])), \
+             ([80 (4)], ([70 (2)], [60 (1)], ), [90 (1)]))"
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut tree = OrderStatisticTree::new();
+        for value in 0..1000 {
+            tree.insert((value * 7919) % 1000);
+        }
+        assert_eq!(check(&tree.root), 12);
+        for value in (0..1000).filter(|value| value % 2 == 0) {
+            assert!(tree.remove(value));
+            check(&tree.root);
+        }
+        assert!(!tree.remove(0));
+        assert_eq!(tree.len(), 500);
+        assert_eq!(tree.select(0), Some(1));
+        assert_eq!(tree.select(499), Some(999));
+        assert_eq!(tree.rank(501), 250);
+        assert_eq!(tree.count_range(100..200), 50);
+
+        for k in (0..500).rev() {
+            let value = tree.select(k).unwrap();
+            assert!(tree.remove(value));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.len(), 0);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::ops::Add;
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNodeRef};
+
+/// Follows `next` links from `start` downwards, collecting every node on the way.
+fn follow_chain<T: Sized + Copy>(
+    start: Option<TreeNodeRef<T>>,
+    next: &HashMap<Uuid, Option<TreeNodeRef<T>>>,
+) -> Vec<TreeNodeRef<T>> {
+    let mut chain: Vec<TreeNodeRef<T>> = Vec::new();
+    let mut current = start;
+    while let Some(node_ref) = current {
+        current = next.get(&node_ref.borrow().id).cloned().flatten();
+        chain.push(node_ref);
+    }
+    chain
+}
+
+/// Joins two downward chains hanging off `node` into a single path running from the bottom of
+/// `left_chain`, up through `node` and down to the bottom of `right_chain`.
+fn join_chains<T: Sized + Copy>(
+    mut left_chain: Vec<TreeNodeRef<T>>,
+    node: TreeNodeRef<T>,
+    right_chain: Vec<TreeNodeRef<T>>,
+) -> Vec<TreeNodeRef<T>> {
+    left_chain.reverse();
+    left_chain.push(node);
+    left_chain.extend(right_chain);
+    left_chain
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// The longest path between any two nodes of the tree, as its length in edges and the nodes
+    /// along it from one end to the other. A lone root has a diameter of 0 and a path of just the
+    /// root.
+    pub fn diameter(&self) -> (usize, Vec<TreeNodeRef<T>>) {
+        // For every node, the height of its subtree and the child on the way to its deepest leaf.
+        let mut heights: HashMap<Uuid, usize> = HashMap::new();
+        let mut next: HashMap<Uuid, Option<TreeNodeRef<T>>> = HashMap::new();
+
+        let mut best_length = 0;
+        let mut best: (
+            Option<TreeNodeRef<T>>,
+            TreeNodeRef<T>,
+            Option<TreeNodeRef<T>>,
+        ) = (None, self.root.clone(), None);
+
+        for (node_ref, _) in self.pre_order_with_parents().into_iter().rev() {
+            let node = node_ref.borrow();
+            let reach = |child: &Option<TreeNodeRef<T>>| -> usize {
+                child
+                    .as_ref()
+                    .map_or(0, |child| heights[&child.borrow().id] + 1)
+            };
+            let left_reach = reach(&node.left);
+            let right_reach = reach(&node.right);
+
+            if left_reach + right_reach > best_length {
+                best_length = left_reach + right_reach;
+                best = (node.left.clone(), node_ref.clone(), node.right.clone());
+            }
+
+            let deepest = if left_reach >= right_reach {
+                node.left.clone()
+            } else {
+                node.right.clone()
+            };
+            heights.insert(node.id, left_reach.max(right_reach));
+            next.insert(node.id, deepest);
+        }
+
+        let (left, node, right) = best;
+        let path = join_chains(follow_chain(left, &next), node, follow_chain(right, &next));
+        (best_length, path)
+    }
+
+    /// The nodes on the path from the node with id `a` to the node with id `b`, both included.
+    /// Returns `None` if either id is not in the tree.
+    pub fn path_between(&self, a: Uuid, b: Uuid) -> Option<Vec<TreeNodeRef<T>>> {
+        let mut parents: HashMap<Uuid, Option<TreeNodeRef<T>>> = HashMap::new();
+        let mut nodes: HashMap<Uuid, TreeNodeRef<T>> = HashMap::new();
+        for (node_ref, parent) in self.pre_order_with_parents() {
+            let id = node_ref.borrow().id;
+            parents.insert(id, parent);
+            nodes.insert(id, node_ref);
+        }
+
+        let ancestors = |id: Uuid| -> Vec<TreeNodeRef<T>> {
+            let mut chain: Vec<TreeNodeRef<T>> = Vec::new();
+            let mut current = nodes.get(&id).cloned();
+            while let Some(node_ref) = current {
+                current = parents[&node_ref.borrow().id].clone();
+                chain.push(node_ref);
+            }
+            chain
+        };
+
+        if !nodes.contains_key(&a) || !nodes.contains_key(&b) {
+            return None;
+        }
+        let mut from_a = ancestors(a);
+        let mut from_b = ancestors(b);
+
+        // Both chains end at the root, drop the shared tail but keep the lowest common ancestor.
+        let mut common = None;
+        while let (Some(last_a), Some(last_b)) = (from_a.last(), from_b.last()) {
+            if !Rc::ptr_eq(last_a, last_b) {
+                break;
+            }
+            from_b.pop();
+            common = from_a.pop();
+        }
+
+        from_a.extend(common);
+        from_a.extend(from_b.into_iter().rev());
+        Some(from_a)
+    }
+
+    /// Every path from the root down to a leaf, ordered from the leftmost leaf to the rightmost.
+    pub fn root_to_leaf_paths(&self) -> Vec<Vec<TreeNodeRef<T>>> {
+        self.leaf_paths(|_| true)
+    }
+
+    /// The root-to-leaf paths for whose leaf `keep` returns true.
+    fn leaf_paths(
+        &self,
+        mut keep: impl FnMut(&TreeNodeRef<T>) -> bool,
+    ) -> Vec<Vec<TreeNodeRef<T>>> {
+        let mut parents: HashMap<Uuid, Option<TreeNodeRef<T>>> = HashMap::new();
+        let mut paths: Vec<Vec<TreeNodeRef<T>>> = Vec::new();
+        for (node_ref, parent) in self.pre_order_with_parents() {
+            parents.insert(node_ref.borrow().id, parent);
+            if !node_ref.borrow().is_leaf() || !keep(&node_ref) {
+                continue;
+            }
+
+            let mut path: Vec<TreeNodeRef<T>> = Vec::new();
+            let mut current = Some(node_ref);
+            while let Some(node_ref) = current {
+                current = parents[&node_ref.borrow().id].clone();
+                path.push(node_ref);
+            }
+            path.reverse();
+            paths.push(path);
+        }
+        paths
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display + Add<Output = T> + PartialOrd> Tree<T> {
+    /// The greatest sum of values along any path between two nodes, together with the nodes of
+    /// that path. A path holds at least one node, so a tree of only negative values returns its
+    /// largest single value.
+    pub fn max_path_sum(&self) -> (T, Vec<TreeNodeRef<T>>) {
+        // For every node, the best sum of a path starting at it and going down, and the child
+        // that path continues to.
+        let mut gains: HashMap<Uuid, T> = HashMap::new();
+        let mut next: HashMap<Uuid, Option<TreeNodeRef<T>>> = HashMap::new();
+
+        let root_value = self.root.borrow().value;
+        let mut best_sum = root_value;
+        let mut best: (
+            Option<TreeNodeRef<T>>,
+            TreeNodeRef<T>,
+            Option<TreeNodeRef<T>>,
+        ) = (None, self.root.clone(), None);
+
+        for (node_ref, _) in self.pre_order_with_parents().into_iter().rev() {
+            let node = node_ref.borrow();
+            let value = node.value;
+            // A child is only worth including if its gain is positive, i.e. it adds to `value`.
+            let gain = |child: &Option<TreeNodeRef<T>>| -> Option<(T, TreeNodeRef<T>)> {
+                let child = child.as_ref()?;
+                let child_gain = gains[&child.borrow().id];
+                (value + child_gain > value).then(|| (child_gain, child.clone()))
+            };
+            let left_gain = gain(&node.left);
+            let right_gain = gain(&node.right);
+
+            let mut through = value;
+            if let Some((left, _)) = &left_gain {
+                through = through + *left;
+            }
+            if let Some((right, _)) = &right_gain {
+                through = through + *right;
+            }
+            if through > best_sum {
+                best_sum = through;
+                best = (
+                    left_gain.as_ref().map(|(_, child)| child.clone()),
+                    node_ref.clone(),
+                    right_gain.as_ref().map(|(_, child)| child.clone()),
+                );
+            }
+
+            let deepest = match (left_gain, right_gain) {
+                (Some(left), Some(right)) => Some(if left.0 >= right.0 { left } else { right }),
+                (left, right) => left.or(right),
+            };
+            gains.insert(
+                node.id,
+                deepest
+                    .as_ref()
+                    .map_or(value, |(child_gain, _)| value + *child_gain),
+            );
+            next.insert(node.id, deepest.map(|(_, child)| child));
+        }
+
+        let (left, node, right) = best;
+        let path = join_chains(follow_chain(left, &next), node, follow_chain(right, &next));
+        (best_sum, path)
+    }
+
+    /// Every root-to-leaf path whose values add up to `target`.
+    pub fn paths_with_sum(&self, target: T) -> Vec<Vec<TreeNodeRef<T>>> {
+        // Sums from the root down to each node, filled in parent-first.
+        let mut sums: HashMap<Uuid, T> = HashMap::new();
+        for (node_ref, parent) in self.pre_order_with_parents() {
+            let node = node_ref.borrow();
+            let sum = match parent {
+                Some(parent) => sums[&parent.borrow().id] + node.value,
+                None => node.value,
+            };
+            sums.insert(node.id, sum);
+        }
+        self.leaf_paths(|leaf| sums[&leaf.borrow().id] == target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    fn values(path: &[TreeNodeRef<i32>]) -> Vec<i32> {
+        path.iter().map(|node| node.borrow().value).collect()
+    }
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> (Tree<i32>, Vec<TreeNodeRef<i32>>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8.clone()));
+        let node3 = TreeNode::new_rc(3, None, Some(node6.clone()));
+        let node5 = TreeNode::new_rc(5, Some(node7.clone()), None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+
+        let tree = Tree::new(node1.clone());
+        (
+            tree,
+            vec![node1, node2, node3, node4, node5, node6, node7, node8],
+        )
+    }
+
+    #[test]
+    fn diameter() {
+        let (tree, nodes) = test_tree();
+        let (length, path) = tree.diameter();
+        assert_eq!(length, 6);
+        assert_eq!(values(&path), vec![7, 5, 2, 1, 3, 6, 8]);
+
+        let leaf = Tree::new(nodes[3].clone());
+        let (length, path) = leaf.diameter();
+        assert_eq!(length, 0);
+        assert_eq!(values(&path), vec![4]);
+    }
+
+    #[test]
+    fn path_between() {
+        let (tree, nodes) = test_tree();
+        let id = |index: usize| nodes[index].borrow().get_id();
+
+        let path = tree.path_between(id(3), id(6)).unwrap();
+        assert_eq!(values(&path), vec![4, 2, 5, 7]);
+
+        let path = tree.path_between(id(7), id(4)).unwrap();
+        assert_eq!(values(&path), vec![8, 6, 3, 1, 2, 5]);
+
+        let path = tree.path_between(id(4), id(4)).unwrap();
+        assert_eq!(values(&path), vec![5]);
+
+        let stranger = TreeNode::new(9, None, None);
+        assert!(tree.path_between(id(0), stranger.get_id()).is_none());
+    }
+
+    #[test]
+    fn root_to_leaf_paths() {
+        let (tree, _) = test_tree();
+        let paths: Vec<Vec<i32>> = tree
+            .root_to_leaf_paths()
+            .iter()
+            .map(|p| values(p))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![vec![1, 2, 4], vec![1, 2, 5, 7], vec![1, 3, 6, 8]]
+        );
+    }
+
+    #[test]
+    fn path_sums() {
+        let (tree, _) = test_tree();
+        let (sum, path) = tree.max_path_sum();
+        assert_eq!(sum, 32);
+        assert_eq!(values(&path), vec![7, 5, 2, 1, 3, 6, 8]);
+
+        let paths: Vec<Vec<i32>> = tree.paths_with_sum(7).iter().map(|p| values(p)).collect();
+        assert_eq!(paths, vec![vec![1, 2, 4]]);
+        assert!(tree.paths_with_sum(100).is_empty());
+
+        // Test tree:
+        //                -10
+        //                / \
+        //               9   20
+        //                  /  \
+        //                 15   7
+        //
+        let node15 = TreeNode::new_rc(15, None, None);
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node20 = TreeNode::new_rc(20, Some(node15), Some(node7));
+        let node9 = TreeNode::new_rc(9, None, None);
+        let tree = Tree::new(TreeNode::new_rc(-10, Some(node9), Some(node20)));
+        let (sum, path) = tree.max_path_sum();
+        assert_eq!(sum, 42);
+        assert_eq!(values(&path), vec![15, 20, 7]);
+
+        let negative = Tree::new(TreeNode::new_rc(
+            -3,
+            Some(TreeNode::new_rc(-1, None, None)),
+            None,
+        ));
+        let (sum, path) = negative.max_path_sum();
+        assert_eq!(sum, -1);
+        assert_eq!(values(&path), vec![-1]);
+    }
+}
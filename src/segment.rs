@@ -0,0 +1,365 @@
+use std::cell::RefCell;
+use std::fmt::{self, Debug, Display};
+use std::ops::Range;
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// A node of a `SegmentTree`, covering `start..end` of the array.
+#[derive(Debug, Clone)]
+struct SegmentNode<T> {
+    start: usize,
+    end: usize,
+    value: T,
+    /// A value assigned to the whole segment that has not been passed on to the children yet.
+    pending: Option<T>,
+    /// Indexes of the children, `None` for a single element.
+    children: Option<(usize, usize)>,
+}
+
+/// The payload of the trees exported by `SegmentTree::to_tree`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment<T> {
+    pub start: usize,
+    pub end: usize,
+    pub value: T,
+    pub pending: Option<T>,
+}
+
+/// Shows as `start..end: value`, followed by `(= pending)` when an assignment is pending.
+impl<T: Display> Display for Segment<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}: {}", self.start, self.end, self.value)?;
+        if let Some(pending) = &self.pending {
+            write!(f, " (= {})", pending)?;
+        }
+        Ok(())
+    }
+}
+
+/// Range queries over an array, combining elements with an associative operation such as sum,
+/// min, max or gcd.
+///
+/// Ranges can be assigned a value lazily: the assignment is kept on the largest segments inside
+/// the range and only passed down when a later update splits them. As the operation only needs
+/// to be associative, the value of `n` equal elements is computed by repeated doubling.
+pub struct SegmentTree<T, Op> {
+    /// The segments, children before their parents, so the root is last.
+    nodes: Vec<SegmentNode<T>>,
+    op: Op,
+}
+
+impl<T: Copy, Op: Fn(T, T) -> T> SegmentTree<T, Op> {
+    /// Builds a segment tree over `values`. Fails if `values` is empty, as there is no identity
+    /// element to answer queries with.
+    pub fn new(values: &[T], op: Op) -> Result<Self, String> {
+        if values.is_empty() {
+            return Err("Attempted to build a segment tree without values".to_string());
+        }
+        let mut tree = SegmentTree {
+            nodes: Vec::with_capacity(2 * values.len() - 1),
+            op,
+        };
+        tree.build(values, 0, values.len());
+        Ok(tree)
+    }
+
+    fn build(&mut self, values: &[T], start: usize, end: usize) -> usize {
+        let (value, children) = if end - start == 1 {
+            (values[start], None)
+        } else {
+            let middle = (start + end) / 2;
+            let left = self.build(values, start, middle);
+            let right = self.build(values, middle, end);
+            let value = (self.op)(self.nodes[left].value, self.nodes[right].value);
+            (value, Some((left, right)))
+        };
+        self.nodes.push(SegmentNode {
+            start,
+            end,
+            value,
+            pending: None,
+            children,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn root(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// The number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.nodes[self.root()].end
+    }
+
+    /// Always false, a segment tree holds at least one element.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    fn check_range(&self, range: &Range<usize>, action: &str) -> Result<(), String> {
+        if range.start >= range.end {
+            return Err(format!("Attempted to {} an empty range", action));
+        }
+        if range.end > self.len() {
+            return Err(format!(
+                "Attempted to {} range {:?} outside of 0..{}",
+                action,
+                range,
+                self.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Combines the elements in `range`.
+    pub fn query(&self, range: Range<usize>) -> Result<T, String> {
+        self.check_range(&range, "query")?;
+        Ok(self
+            .query_at(self.root(), &range, &mut Vec::new())
+            .expect("a valid range overlaps the root"))
+    }
+
+    /// Collects the indexes of the segments the query looks at in `visited`.
+    fn query_at(&self, at: usize, range: &Range<usize>, visited: &mut Vec<usize>) -> Option<T> {
+        let node = &self.nodes[at];
+        if range.end <= node.start || node.end <= range.start {
+            return None;
+        }
+        visited.push(at);
+        if range.start <= node.start && node.end <= range.end {
+            return Some(node.value);
+        }
+        if let Some(pending) = node.pending {
+            let overlap = range.end.min(node.end) - range.start.max(node.start);
+            return Some(self.repeat(pending, overlap));
+        }
+        let (left, right) = node
+            .children
+            .expect("a partly covered segment has children");
+        match (
+            self.query_at(left, range, visited),
+            self.query_at(right, range, visited),
+        ) {
+            (Some(left), Some(right)) => Some((self.op)(left, right)),
+            (left, right) => left.or(right),
+        }
+    }
+
+    /// Sets the element at `index`.
+    pub fn update(&mut self, index: usize, value: T) -> Result<(), String> {
+        if index >= self.len() {
+            return Err(format!(
+                "Attempted to update index {} outside of 0..{}",
+                index,
+                self.len()
+            ));
+        }
+        self.assign_at(self.root(), &(index..index + 1), value);
+        Ok(())
+    }
+
+    /// Sets every element in `range` to `value`.
+    pub fn assign(&mut self, range: Range<usize>, value: T) -> Result<(), String> {
+        self.check_range(&range, "assign")?;
+        self.assign_at(self.root(), &range, value);
+        Ok(())
+    }
+
+    fn assign_at(&mut self, at: usize, range: &Range<usize>, value: T) {
+        let node = &self.nodes[at];
+        if range.end <= node.start || node.end <= range.start {
+            return;
+        }
+        if range.start <= node.start && node.end <= range.end {
+            self.set_whole(at, value);
+            return;
+        }
+        let (left, right) = node
+            .children
+            .expect("a partly covered segment has children");
+        if let Some(pending) = self.nodes[at].pending.take() {
+            self.set_whole(left, pending);
+            self.set_whole(right, pending);
+        }
+        self.assign_at(left, range, value);
+        self.assign_at(right, range, value);
+        self.nodes[at].value = (self.op)(self.nodes[left].value, self.nodes[right].value);
+    }
+
+    fn set_whole(&mut self, at: usize, value: T) {
+        let combined = self.repeat(value, self.nodes[at].end - self.nodes[at].start);
+        let node = &mut self.nodes[at];
+        node.value = combined;
+        node.pending = node.children.map(|_| value);
+    }
+
+    /// `value` combined with itself to stand for `count` equal elements.
+    fn repeat(&self, value: T, mut count: usize) -> T {
+        let mut result: Option<T> = None;
+        let mut power = value;
+        loop {
+            if count & 1 == 1 {
+                result = Some(result.map_or(power, |result| (self.op)(result, power)));
+            }
+            count >>= 1;
+            if count == 0 {
+                return result.expect("segments are never empty");
+            }
+            power = (self.op)(power, power);
+        }
+    }
+}
+
+impl<T: Copy + Debug + Display, Op: Fn(T, T) -> T> SegmentTree<T, Op> {
+    /// The segments as a regular tree, for export with `Tree::save_typst` and friends.
+    pub fn to_tree(&self) -> Tree<Segment<T>> {
+        self.to_tree_with_ids().0
+    }
+
+    /// Also returns the id of the tree node made for each segment.
+    fn to_tree_with_ids(&self) -> (Tree<Segment<T>>, Vec<Uuid>) {
+        let mut built: Vec<Option<TreeNodeRef<Segment<T>>>> = vec![None; self.nodes.len()];
+        let mut ids: Vec<Uuid> = Vec::with_capacity(self.nodes.len());
+        for (at, node) in self.nodes.iter().enumerate() {
+            let (left, right) = match node.children {
+                Some((left, right)) => (built[left].take(), built[right].take()),
+                None => (None, None),
+            };
+            let segment = Segment {
+                start: node.start,
+                end: node.end,
+                value: node.value,
+                pending: node.pending,
+            };
+            let tree_node = TreeNode::new(segment, left, right);
+            ids.push(tree_node.id);
+            built[at] = Some(Rc::new(RefCell::new(tree_node)));
+        }
+        let root = built.pop().flatten().expect("root is built");
+        (Tree::new(root), ids)
+    }
+
+    /// The exported tree with the ids of the segments a query of `range` looks at.
+    pub fn query_trace(
+        &self,
+        range: Range<usize>,
+    ) -> Result<(Tree<Segment<T>>, Vec<Uuid>), String> {
+        self.check_range(&range, "query")?;
+        let mut visited: Vec<usize> = Vec::new();
+        self.query_at(self.root(), &range, &mut visited);
+        let (tree, ids) = self.to_tree_with_ids();
+        Ok((tree, visited.into_iter().map(|at| ids[at]).collect()))
+    }
+
+    /// The segments in Typst format, with those a query of `range` looks at drawn in red.
+    pub fn query_typst(&self, range: Range<usize>) -> Result<String, String> {
+        let (tree, visited) = self.query_trace(range)?;
+        Ok(tree.typst_string_highlighted(&visited))
+    }
+
+    /// The segments drawn for the terminal, with those a query of `range` looks at marked with
+    /// asterisks.
+    pub fn query_terminal(&self, range: Range<usize>, width: usize) -> Result<String, String> {
+        let (tree, visited) = self.query_trace(range)?;
+        Ok(tree.terminal_string_highlighted(width, &visited))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    #[test]
+    fn queries_and_updates() {
+        let values = [5, 3, 8, 6, 1];
+        let mut sum = SegmentTree::new(&values, |a: i64, b| a + b).unwrap();
+        let min = SegmentTree::new(&values, |a: i64, b: i64| a.min(b)).unwrap();
+        assert_eq!(sum.len(), 5);
+        assert_eq!(sum.query(1..4), Ok(17));
+        assert_eq!(sum.query(0..5), Ok(23));
+        assert_eq!(min.query(0..3), Ok(3));
+        assert_eq!(min.query(2..5), Ok(1));
+
+        sum.update(2, 10).unwrap();
+        assert_eq!(sum.query(2..3), Ok(10));
+        assert_eq!(sum.query(1..4), Ok(19));
+
+        assert_eq!(
+            sum.query(3..3),
+            Err("Attempted to query an empty range".to_string())
+        );
+        assert_eq!(
+            sum.query(2..9),
+            Err("Attempted to query range 2..9 outside of 0..5".to_string())
+        );
+        assert!(sum.update(5, 0).is_err());
+        assert!(SegmentTree::new(&[], |a: i64, b| a + b).is_err());
+
+        let gcds = SegmentTree::new(&[12, 18, 24, 9, 27], gcd).unwrap();
+        assert_eq!(gcds.query(0..3), Ok(6));
+        assert_eq!(gcds.query(3..5), Ok(9));
+        assert_eq!(gcds.query(0..5), Ok(3));
+    }
+
+    #[test]
+    fn lazy_assign() {
+        let values: Vec<i64> = (0..10).collect();
+        let mut sum = SegmentTree::new(&values, |a: i64, b| a + b).unwrap();
+        let mut max = SegmentTree::new(&values, |a: i64, b: i64| a.max(b)).unwrap();
+        sum.assign(2..8, 1).unwrap();
+        max.assign(2..8, 1).unwrap();
+
+        let mut expected = values.clone();
+        expected[2..8].fill(1);
+        for start in 0..10 {
+            for end in start + 1..=10 {
+                let slice = &expected[start..end];
+                assert_eq!(sum.query(start..end), Ok(slice.iter().sum()));
+                assert_eq!(max.query(start..end), Ok(*slice.iter().max().unwrap()));
+            }
+        }
+
+        // A point update inside an assigned range pushes the assignment down.
+        sum.update(4, 100).unwrap();
+        sum.assign(0..3, 2).unwrap();
+        assert_eq!(sum.query(0..10), Ok(2 * 3 + 1 + 100 + 1 + 1 + 1 + 8 + 9));
+        assert_eq!(sum.query(3..6), Ok(102));
+    }
+
+    #[test]
+    fn debug_export() {
+        let mut sum = SegmentTree::new(&[1, 2, 3, 4], |a: i32, b| a + b).unwrap();
+        assert_eq!(
+            sum.to_tree().typst_string(),
+            "([0..4: 10], ([0..2: 3], [0..1: 1], [1..2: 2]), ([2..4: 7], [2..3: 3], [3..4: 4]))"
+        );
+        assert_eq!(
+            sum.query_typst(1..3).unwrap(),
+            "([#text(fill: red)[0..4: 10]], ([#text(fill: red)[0..2: 3]], [0..1: 1], \
+             [#text(fill: red)[1..2: 2]]), ([#text(fill: red)[2..4: 7]], \
+             [#text(fill: red)[2..3: 3]], [3..4: 4]))"
+        );
+        assert_eq!(
+            sum.query_terminal(0..4, 40).unwrap(),
+            "               *0..4: 10*\n       0..2: 3             2..4: 7\n  0..1: 1   1..2: 2   2..3: 3   3..4: 4"
+        );
+
+        sum.assign(0..4, 5).unwrap();
+        assert_eq!(
+            sum.to_tree().typst_string(),
+            "([0..4: 20 (= 5)], ([0..2: 3], [0..1: 1], [1..2: 2]), ([2..4: 7], [2..3: 3], [3..4: 4]))"
+        );
+    }
+}
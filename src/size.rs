@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNodeRef};
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Starts keeping the subtree size of every node, so that `Tree::size` and
+    /// `Tree::subtree_size` take constant time.
+    ///
+    /// Like the id index, the sizes are kept up to date by the tree's own mutation methods, and
+    /// not by changes made directly through a node's `left`/`right` fields. An update walks up
+    /// from the changed node, which takes O(depth) if the tree also has an id index (see
+    /// `Tree::build_index`) and a pass over the tree otherwise. If it meets a node whose size is
+    /// not known, all sizes are recounted instead.
+    pub fn track_sizes(&mut self) {
+        self.sizes = Some(subtree_sizes(&self.root));
+    }
+
+    /// Stops keeping subtree sizes, they go back to being counted on request.
+    pub fn stop_tracking_sizes(&mut self) {
+        self.sizes = None;
+    }
+
+    /// Whether the tree keeps subtree sizes.
+    pub fn tracks_sizes(&self) -> bool {
+        self.sizes.is_some()
+    }
+
+    /// The number of nodes in the tree. Falls back to counting if the root was replaced without
+    /// going through the tree's own methods, so that its size is not known.
+    pub fn size(&self) -> usize {
+        let root_id = self.root.borrow().id;
+        self.sizes
+            .as_ref()
+            .and_then(|sizes| sizes.get(&root_id).copied())
+            .unwrap_or_else(|| self.root.borrow().size())
+    }
+
+    /// The number of nodes in the subtree of the node with the given id, `None` if it is not in
+    /// the tree.
+    pub fn subtree_size(&self, id: Uuid) -> Option<usize> {
+        match &self.sizes {
+            Some(sizes) => sizes.get(&id).copied(),
+            None => self.get_by_id(id).map(|node_ref| node_ref.borrow().size()),
+        }
+    }
+
+    /// Records that `subtree` was attached below the node with id `parent`.
+    pub(crate) fn sizes_attached(&mut self, subtree: &TreeNodeRef<T>, parent: Uuid) {
+        self.sizes_changed(parent, None, Some(subtree));
+    }
+
+    /// Records that `subtree` was cut off from below the node with id `parent`.
+    pub(crate) fn sizes_detached(&mut self, subtree: &TreeNodeRef<T>, parent: Uuid) {
+        self.sizes_changed(parent, Some(subtree), None);
+    }
+
+    /// Records that `old` was swapped for `new` below the node with id `parent`.
+    pub(crate) fn sizes_replaced(
+        &mut self,
+        old: &TreeNodeRef<T>,
+        new: &TreeNodeRef<T>,
+        parent: Uuid,
+    ) {
+        self.sizes_changed(parent, Some(old), Some(new));
+    }
+
+    /// Updates the sizes of `parent` and its ancestors after `removed` was cut off from below it
+    /// and `added` attached. Recounts everything instead if a size involved is not known, e.g.
+    /// because nodes were added directly through a node's `left`/`right` fields.
+    fn sizes_changed(
+        &mut self,
+        parent: Uuid,
+        removed: Option<&TreeNodeRef<T>>,
+        added: Option<&TreeNodeRef<T>>,
+    ) {
+        if self.sizes.is_none() {
+            return;
+        }
+        let ancestors = self.ancestors(parent);
+        let added = added.map(|subtree| (subtree.borrow().id, subtree_sizes(subtree)));
+        let grown = added.as_ref().map_or(0, |(id, added)| added[id]);
+        let sizes = self.sizes.as_mut().expect("checked above");
+        let shrunk = match removed {
+            Some(subtree) => sizes.get(&subtree.borrow().id).copied(),
+            None => Some(0),
+        };
+        let updated: Option<Vec<(Uuid, usize)>> = ancestors
+            .into_iter()
+            .map(|id| {
+                let size = (sizes.get(&id)? + grown).checked_sub(shrunk?)?;
+                Some((id, size))
+            })
+            .collect();
+        let Some(updated) = updated else {
+            self.refresh_sizes();
+            return;
+        };
+        if let Some(removed) = removed {
+            let mut stack: Vec<TreeNodeRef<T>> = vec![removed.clone()];
+            while let Some(current) = stack.pop() {
+                let node = current.borrow();
+                stack.extend(node.left.clone());
+                stack.extend(node.right.clone());
+                sizes.remove(&node.id);
+            }
+        }
+        sizes.extend(added.into_iter().flat_map(|(_, added)| added));
+        sizes.extend(updated);
+    }
+
+    /// Recounts all sizes after a change too large to follow node by node, if sizes are tracked.
+    pub(crate) fn refresh_sizes(&mut self) {
+        if self.tracks_sizes() {
+            self.track_sizes();
+        }
+    }
+
+    /// The node with the given id followed by its ancestors up to the root.
+    fn ancestors(&self, id: Uuid) -> Vec<Uuid> {
+        let parents: HashMap<Uuid, Uuid> = if self.has_index() {
+            HashMap::new()
+        } else {
+            self.pre_order_with_parents()
+                .into_iter()
+                .filter_map(|(node_ref, parent)| {
+                    parent.map(|parent| (node_ref.borrow().id, parent.borrow().id))
+                })
+                .collect()
+        };
+        let mut ancestors = vec![id];
+        let mut current = id;
+        loop {
+            let parent = match self.indexed_parent(current) {
+                Some(parent) => parent,
+                None => parents.get(&current).copied(),
+            };
+            let Some(parent) = parent else {
+                return ancestors;
+            };
+            ancestors.push(parent);
+            current = parent;
+        }
+    }
+}
+
+/// The size of every node in the subtree of `root`, counted bottom-up.
+fn subtree_sizes<T: Sized + Copy + Debug + Display>(root: &TreeNodeRef<T>) -> HashMap<Uuid, usize> {
+    let mut sizes: HashMap<Uuid, usize> = HashMap::new();
+    for (node_ref, _) in Tree::new(root.clone())
+        .pre_order_with_parents()
+        .into_iter()
+        .rev()
+    {
+        let node = node_ref.borrow();
+        let size = 1 + [&node.left, &node.right]
+            .into_iter()
+            .flatten()
+            .map(|child| sizes[&child.borrow().id])
+            .sum::<usize>();
+        sizes.insert(node.id, size);
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Edit, NodeKey, Side, SubtreeSize, TreeNode};
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /
+    //               7
+    //
+    fn test_tree() -> Tree<i32> {
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, Some(node7), None);
+        let node6 = TreeNode::new_rc(6, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node3 = TreeNode::new_rc(3, None, Some(node6));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    fn id_of(tree: &Tree<i32>, value: i32) -> Uuid {
+        let (node_ref, _) = tree
+            .pre_order_with_parents()
+            .into_iter()
+            .find(|(node_ref, _)| node_ref.borrow().value == value)
+            .unwrap();
+        let id = node_ref.borrow().get_id();
+        id
+    }
+
+    /// Checks the tracked sizes against a fresh count.
+    fn check(tree: &Tree<i32>) {
+        assert_eq!(tree.sizes, Some(subtree_sizes(&tree.root)));
+    }
+
+    #[test]
+    fn untracked_sizes() {
+        let tree = test_tree();
+        let node2 = tree.root.borrow().left.clone().unwrap();
+        assert_eq!(node2.borrow().size(), 4);
+        assert_eq!(node2.size(), 4);
+        assert_eq!(tree.size(), 7);
+        assert_eq!(tree.subtree_size(id_of(&tree, 3)), Some(2));
+        assert_eq!(tree.subtree_size(Uuid::nil()), None);
+        // Still usable afterwards, nothing was consumed.
+        assert_eq!(node2.borrow().value, 2);
+    }
+
+    #[test]
+    fn tracked_through_mutations() {
+        for indexed in [false, true] {
+            let mut tree = test_tree();
+            if indexed {
+                tree.build_index();
+            }
+            tree.track_sizes();
+            assert!(tree.tracks_sizes());
+            assert_eq!(tree.size(), 7);
+            assert_eq!(tree.subtree_size(id_of(&tree, 5)), Some(2));
+
+            tree.insert_left(id_of(&tree, 7), 8).unwrap();
+            check(&tree);
+            assert_eq!(tree.subtree_size(id_of(&tree, 2)), Some(5));
+
+            let detached = tree.remove_subtree(id_of(&tree, 5)).unwrap();
+            check(&tree);
+            assert_eq!(tree.size(), 5);
+
+            tree.graft(id_of(&tree, 3), Side::Left, detached).unwrap();
+            check(&tree);
+            assert_eq!(tree.subtree_size(id_of(&tree, 3)), Some(5));
+
+            let replacement = Tree::new(TreeNode::new_rc(9, None, None));
+            tree.replace_subtree(id_of(&tree, 6), replacement).unwrap();
+            check(&tree);
+
+            tree.prune(|value| *value == 7).unwrap();
+            check(&tree);
+            assert_eq!(tree.size(), 6);
+
+            let id4 = id_of(&tree, 4);
+            let mut cursor = tree.cursor();
+            cursor.move_left().unwrap().insert_right(10).unwrap();
+            cursor.move_left().unwrap().delete_subtree().unwrap();
+            check(&tree);
+            assert_eq!(tree.subtree_size(id4), None);
+
+            let id2 = id_of(&tree, 2);
+            tree.apply_patch(&[Edit::Delete {
+                key: NodeKey::Id(id_of(&tree, 10)),
+            }])
+            .unwrap();
+            check(&tree);
+            assert_eq!(tree.subtree_size(id2), Some(1));
+
+            let new_root = Tree::new(TreeNode::new_rc(0, None, None));
+            let root_id = tree.root.borrow().get_id();
+            tree.replace_subtree(root_id, new_root).unwrap();
+            check(&tree);
+            assert_eq!(tree.size(), 1);
+
+            let copy = tree.deep_clone();
+            assert!(copy.tracks_sizes());
+            check(&copy);
+            check(&tree.deep_clone_with_new_ids());
+
+            tree.stop_tracking_sizes();
+            assert!(!tree.tracks_sizes());
+            assert_eq!(tree.size(), 1);
+        }
+    }
+
+    #[test]
+    fn root_replaced_directly() {
+        let mut tree = test_tree();
+        tree.track_sizes();
+        tree.root = TreeNode::new_rc(8, Some(TreeNode::new_rc(9, None, None)), None);
+        assert_eq!(tree.size(), 2);
+    }
+
+    #[test]
+    fn edit_below_node_added_directly() {
+        // Only without an index, which would not find the new nodes either.
+        let mut tree = test_tree();
+        tree.track_sizes();
+        tree.root.borrow_mut().add_left(8);
+        let id8 = tree.root.borrow().left.as_ref().unwrap().borrow().get_id();
+        tree.insert_left(id8, 9).unwrap();
+        check(&tree);
+        assert_eq!(tree.size(), 5);
+
+        let node3 = tree.root.borrow().right.clone().unwrap();
+        node3.borrow_mut().add_left(10);
+        let replacement = Tree::new(TreeNode::new_rc(11, None, None));
+        tree.replace_subtree(id_of(&tree, 10), replacement).unwrap();
+        check(&tree);
+
+        node3.borrow_mut().add_right(12);
+        tree.remove_subtree(id_of(&tree, 12)).unwrap();
+        check(&tree);
+        assert_eq!(tree.size(), 5);
+    }
+}
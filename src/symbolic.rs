@@ -0,0 +1,396 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::expr::{BinaryOp, ExprNode};
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+type ExprRef = TreeNodeRef<ExprNode>;
+
+fn number(value: f64) -> ExprRef {
+    TreeNode::new_rc(ExprNode::Number(value), None, None)
+}
+
+fn operation(op: BinaryOp, left: ExprRef, right: ExprRef) -> ExprRef {
+    TreeNode::new_rc(ExprNode::Op(op), Some(left), Some(right))
+}
+
+fn number_of(node: &ExprRef) -> Option<f64> {
+    match node.borrow().value {
+        ExprNode::Number(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// The operator of `node` and its two operands, if it is an operation.
+fn operands_of(node: &ExprRef) -> Option<(BinaryOp, ExprRef, ExprRef)> {
+    let node = node.borrow();
+    match (node.value, &node.left, &node.right) {
+        (ExprNode::Op(op), Some(left), Some(right)) => Some((op, left.clone(), right.clone())),
+        _ => None,
+    }
+}
+
+/// An independent copy of a subtree with new ids, for when a derivative needs an operand twice.
+fn fresh(node: &ExprRef) -> ExprRef {
+    Rc::new(RefCell::new(node.borrow().deep_clone_with_new_ids()))
+}
+
+/// Splits a term into its numeric coefficient and the rest, so `3 * x` is `(3, x)` and `x` is
+/// `(1, x)`.
+fn split_term(node: ExprRef) -> (f64, ExprRef) {
+    if let Some((BinaryOp::Mul, left, right)) = operands_of(&node) {
+        if let Some(coefficient) = number_of(&left) {
+            return (coefficient, right);
+        }
+    }
+    (1.0, node)
+}
+
+fn make_term(coefficient: f64, base: ExprRef) -> ExprRef {
+    if coefficient == 1.0 {
+        base
+    } else {
+        operation(BinaryOp::Mul, number(coefficient), base)
+    }
+}
+
+/// Flattens a sum or difference into signed terms, adds up the coefficients of terms that are
+/// the same apart from their coefficient, and rebuilds it with the constant last.
+fn collect_terms(op: BinaryOp, left: ExprRef, right: ExprRef) -> ExprRef {
+    let mut terms: Vec<(f64, ExprRef)> = Vec::new();
+    let mut constant = 0.0;
+    let right_sign = if op == BinaryOp::Sub { -1.0 } else { 1.0 };
+    let mut stack: Vec<(ExprRef, f64)> = vec![(right, right_sign), (left, 1.0)];
+    while let Some((node, sign)) = stack.pop() {
+        match operands_of(&node) {
+            Some((BinaryOp::Add, left, right)) => {
+                stack.push((right, sign));
+                stack.push((left, sign));
+                continue;
+            }
+            Some((BinaryOp::Sub, left, right)) => {
+                stack.push((right, -sign));
+                stack.push((left, sign));
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(value) = number_of(&node) {
+            constant += sign * value;
+            continue;
+        }
+        let (coefficient, base) = split_term(node);
+        match terms.iter_mut().find(|(_, known)| *known == base) {
+            Some((known_coefficient, _)) => *known_coefficient += sign * coefficient,
+            None => terms.push((sign * coefficient, base)),
+        }
+    }
+
+    let mut sum: Option<ExprRef> = None;
+    for (coefficient, base) in terms {
+        if coefficient == 0.0 {
+            continue;
+        }
+        sum = Some(match sum {
+            None => make_term(coefficient, base),
+            Some(sum) if coefficient < 0.0 => {
+                operation(BinaryOp::Sub, sum, make_term(-coefficient, base))
+            }
+            Some(sum) => operation(BinaryOp::Add, sum, make_term(coefficient, base)),
+        });
+    }
+    match sum {
+        None => number(constant),
+        Some(sum) if constant < 0.0 => operation(BinaryOp::Sub, sum, number(-constant)),
+        Some(sum) if constant > 0.0 => operation(BinaryOp::Add, sum, number(constant)),
+        Some(sum) => sum,
+    }
+}
+
+/// Folds an operation on two numbers into a number. Results that are infinite or NaN, e.g. from
+/// division by zero, are left unfolded, since they cannot be written as a number literal.
+fn fold_numbers(op: BinaryOp, left: &ExprRef, right: &ExprRef) -> Option<ExprRef> {
+    let (a, b) = (number_of(left)?, number_of(right)?);
+    let result = op.apply(a, b);
+    result.is_finite().then(|| number(result))
+}
+
+/// Simplifies one operation whose operands are already simplified.
+fn simplify_operation(op: BinaryOp, left: ExprRef, right: ExprRef) -> ExprRef {
+    if let Some(folded) = fold_numbers(op, &left, &right) {
+        return folded;
+    }
+    let (a, b) = (number_of(&left), number_of(&right));
+    match op {
+        BinaryOp::Add | BinaryOp::Sub => collect_terms(op, left, right),
+        BinaryOp::Mul => {
+            if a == Some(0.0) || b == Some(0.0) {
+                return number(0.0);
+            }
+            if a == Some(1.0) {
+                return right;
+            }
+            if b == Some(1.0) {
+                return left;
+            }
+            // Numbers go to the left, where `split_term` looks for coefficients.
+            let (left, right) = if b.is_some() {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            if let Some(a) = number_of(&left) {
+                if let Some((BinaryOp::Mul, inner_left, inner_right)) = operands_of(&right) {
+                    if let Some(c) = number_of(&inner_left) {
+                        return make_term(a * c, inner_right);
+                    }
+                }
+            }
+            if left == right {
+                return operation(BinaryOp::Pow, left, number(2.0));
+            }
+            operation(BinaryOp::Mul, left, right)
+        }
+        BinaryOp::Div => {
+            if b == Some(1.0) || a == Some(0.0) {
+                return left;
+            }
+            if left == right {
+                return number(1.0);
+            }
+            operation(BinaryOp::Div, left, right)
+        }
+        BinaryOp::Pow => {
+            if b == Some(0.0) || a == Some(1.0) {
+                return number(1.0);
+            }
+            if b == Some(1.0) {
+                return left;
+            }
+            operation(BinaryOp::Pow, left, right)
+        }
+    }
+}
+
+/// A node of the expression and its derivative, while differentiating bottom-up.
+struct Derived {
+    expr: ExprRef,
+    derivative: ExprRef,
+    /// Whether `expr` contains the variable being differentiated for.
+    varies: bool,
+}
+
+impl Tree<ExprNode> {
+    /// The node values in post-order, after checking that operators have two children and
+    /// numbers and variables none.
+    fn checked_post_order(&self) -> Result<Vec<ExprNode>, String> {
+        self.post_order_nodes()
+            .iter()
+            .map(|node_ref| {
+                let node = node_ref.borrow();
+                match (node.value, node.count_children()) {
+                    (ExprNode::Op(_), 2) | (ExprNode::Number(_) | ExprNode::Variable(_), 0) => {
+                        Ok(node.value)
+                    }
+                    _ => Err("Attempted to transform a malformed expression".to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// Builds a new expression bottom-up, combining every operation's already rebuilt operands
+    /// with `combine`.
+    fn rebuild_expr(
+        &self,
+        combine: impl Fn(BinaryOp, ExprRef, ExprRef) -> ExprRef,
+    ) -> Result<Tree<ExprNode>, String> {
+        let mut built: Vec<ExprRef> = Vec::new();
+        for value in self.checked_post_order()? {
+            let node = match value {
+                ExprNode::Op(op) => {
+                    let right = built.pop().expect("operands are built first");
+                    let left = built.pop().expect("operands are built first");
+                    combine(op, left, right)
+                }
+                leaf => TreeNode::new_rc(leaf, None, None),
+            };
+            built.push(node);
+        }
+        Ok(Tree::new(built.pop().expect("the root is built last")))
+    }
+
+    /// A copy of the expression with every operation on two numbers replaced by its result, e.g.
+    /// `2 * 3 + x` becomes `6 + x`. Operations whose result would be infinite or NaN, such as
+    /// divisions by zero or `(0 - 8) ^ 0.5`, are kept as they are.
+    pub fn fold_constants(&self) -> Result<Tree<ExprNode>, String> {
+        self.rebuild_expr(|op, left, right| {
+            fold_numbers(op, &left, &right).unwrap_or_else(|| operation(op, left, right))
+        })
+    }
+
+    /// A simplified copy of the expression. Folds constants, removes neutral elements such as
+    /// `x * 1`, `x + 0` and `x ^ 1`, cancels `x - x` and `x / x`, and collects like terms, so
+    /// `x + 1 + 2 * x` becomes `3 * x + 1`. Assumes that divisors are not zero.
+    pub fn simplify(&self) -> Result<Tree<ExprNode>, String> {
+        self.rebuild_expr(simplify_operation)
+    }
+
+    /// The derivative of the expression with respect to `variable`, simplified. Fails for
+    /// powers whose exponent contains `variable`, whose derivative needs a logarithm.
+    pub fn differentiate(&self, variable: char) -> Result<Tree<ExprNode>, String> {
+        let mut stack: Vec<Derived> = Vec::new();
+        for value in self.checked_post_order()? {
+            let derived = match value {
+                ExprNode::Number(_) => Derived {
+                    expr: TreeNode::new_rc(value, None, None),
+                    derivative: number(0.0),
+                    varies: false,
+                },
+                ExprNode::Variable(name) => Derived {
+                    expr: TreeNode::new_rc(value, None, None),
+                    derivative: number(if name == variable { 1.0 } else { 0.0 }),
+                    varies: name == variable,
+                },
+                ExprNode::Op(op) => {
+                    let g = stack.pop().expect("operands are derived first");
+                    let f = stack.pop().expect("operands are derived first");
+                    let derivative = match op {
+                        BinaryOp::Add | BinaryOp::Sub => operation(op, f.derivative, g.derivative),
+                        // (f * g)' = f' * g + f * g'
+                        BinaryOp::Mul => operation(
+                            BinaryOp::Add,
+                            operation(BinaryOp::Mul, f.derivative, fresh(&g.expr)),
+                            operation(BinaryOp::Mul, fresh(&f.expr), g.derivative),
+                        ),
+                        // (f / g)' = (f' * g - f * g') / g ^ 2
+                        BinaryOp::Div => operation(
+                            BinaryOp::Div,
+                            operation(
+                                BinaryOp::Sub,
+                                operation(BinaryOp::Mul, f.derivative, fresh(&g.expr)),
+                                operation(BinaryOp::Mul, fresh(&f.expr), g.derivative),
+                            ),
+                            operation(BinaryOp::Pow, fresh(&g.expr), number(2.0)),
+                        ),
+                        // (f ^ g)' = g * f ^ (g - 1) * f' for g not depending on the variable
+                        BinaryOp::Pow => {
+                            if g.varies {
+                                return Err(format!(
+                                    "Attempted to differentiate a power with '{}' in the exponent",
+                                    variable
+                                ));
+                            }
+                            let lowered = operation(BinaryOp::Sub, fresh(&g.expr), number(1.0));
+                            operation(
+                                BinaryOp::Mul,
+                                operation(
+                                    BinaryOp::Mul,
+                                    fresh(&g.expr),
+                                    operation(BinaryOp::Pow, fresh(&f.expr), lowered),
+                                ),
+                                f.derivative,
+                            )
+                        }
+                    };
+                    Derived {
+                        expr: operation(op, f.expr, g.expr),
+                        derivative,
+                        varies: f.varies || g.varies,
+                    }
+                }
+            };
+            stack.push(derived);
+        }
+        let derived = stack.pop().expect("the root is derived last");
+        Tree::new(derived.derivative).simplify()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn simplified(input: &str) -> String {
+        Tree::parse_expr(input)
+            .unwrap()
+            .simplify()
+            .unwrap()
+            .to_infix()
+    }
+
+    #[test]
+    fn fold_constants() {
+        let tree = Tree::parse_expr("2 * 3 + x / (4 - 2 ^ 2)").unwrap();
+        assert_eq!(tree.fold_constants().unwrap().to_infix(), "(6 + (x / 0))");
+        let tree = Tree::parse_expr("1 / (2 - 2)").unwrap();
+        assert_eq!(tree.fold_constants().unwrap().to_infix(), "(1 / 0)");
+    }
+
+    #[test]
+    fn simplify() {
+        assert_eq!(simplified("x * 1 + 0"), "x");
+        assert_eq!(simplified("0 * y + x ^ 1"), "x");
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("(x + y) / (x + y)"), "1");
+        assert_eq!(simplified("x * 2 + 3 * x"), "(5 * x)");
+        assert_eq!(simplified("x + 1 + 2 * x - y + 4"), "(((3 * x) - y) + 5)");
+        assert_eq!(simplified("2 * (3 * x) * x ^ 0"), "(6 * x)");
+        assert_eq!(simplified("y * y"), "(y ^ 2)");
+    }
+
+    #[test]
+    fn negative_results_round_trip() {
+        for input in [
+            "1 - 3",
+            "0 - x",
+            "x - 3 * x",
+            "2 - 5 * y ^ 3",
+            "y - (4 + x)",
+            "0 ^ -1",
+            "(0 - 8) ^ 0.5",
+            "1 / (2 - 2) + x",
+        ] {
+            let tree = Tree::parse_expr(input).unwrap();
+            for result in [tree.simplify().unwrap(), tree.fold_constants().unwrap()] {
+                assert_eq!(Tree::parse_expr(&result.to_infix()).unwrap(), result);
+            }
+        }
+        assert_eq!(simplified("1 - 3"), "-2");
+        assert_eq!(simplified("0 - x"), "(-1 * x)");
+        assert_eq!(simplified("0 ^ -1"), "(0 ^ -1)");
+        assert_eq!(simplified("(0 - 8) ^ 0.5"), "(-8 ^ 0.5)");
+    }
+
+    #[test]
+    fn differentiate() {
+        let tree = Tree::parse_expr("x ^ 3 + 2 * x * y").unwrap();
+        let derivative = tree.differentiate('x').unwrap();
+        assert_eq!(derivative.to_infix(), "((3 * (x ^ 2)) + (2 * y))");
+        assert_eq!(tree.differentiate('z').unwrap().to_infix(), "0");
+
+        // Compare the quotient rule against a finite difference.
+        let quotient = Tree::parse_expr("(x ^ 2 + 1) / (x - 3)").unwrap();
+        let derivative = quotient.differentiate('x').unwrap();
+        let at = |tree: &Tree<ExprNode>, x: f64| tree.evaluate(&HashMap::from([('x', x)])).unwrap();
+        let step = 1e-6;
+        let estimate = (at(&quotient, 1.5 + step) - at(&quotient, 1.5 - step)) / (2.0 * step);
+        assert!((at(&derivative, 1.5) - estimate).abs() < 1e-4);
+        assert!(derivative.validate().is_valid());
+
+        assert_eq!(
+            Tree::parse_expr("2 ^ x").unwrap().differentiate('x').err(),
+            Some("Attempted to differentiate a power with 'x' in the exponent".to_string())
+        );
+    }
+
+    #[test]
+    fn before_and_after() {
+        let before = Tree::parse_expr("x * 1 + 0").unwrap();
+        let after = before.simplify().unwrap();
+        let document = before.typst_side_by_side(&after);
+        assert!(document.contains("#let before = (\n([+], ([×], [x], [1]), [0])\n)"));
+        assert!(document.contains("#let after = (\n[x]\n)"));
+        assert!(document.contains("tree.tree(after, "));
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::{Debug, Display};
+
+use crate::{Tree, TreeNode};
+
+/// Width used by `Tree::print_terminal` when the terminal size cannot be determined, e.g. when
+/// output is piped to a file.
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Lays the tree out as plain text, one line per level, with each node centred over the slot
+    /// it would occupy in a complete tree of the same depth:
+    ///
+    /// ```text
+    ///      1
+    ///   2     3
+    ///  4 5
+    /// ```
+    ///
+    /// Labels that do not fit in their slot overwrite their neighbours, so deep or wide trees need
+    /// a correspondingly wide `width`.
+    pub fn terminal_string(&self, width: usize) -> String {
+        self.terminal_string_with(width, |node| node.value.to_string())
+    }
+
+    /// Like `Tree::terminal_string`, but each node is drawn with the text returned by `label`.
+    pub fn terminal_string_with(
+        &self,
+        width: usize,
+        label: impl Fn(&TreeNode<T>) -> String,
+    ) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for level in self.positioned_levels() {
+            let mut line: Vec<char> = vec![' '; width];
+            for (node_ref, centre) in level {
+                let label: Vec<char> = label(&node_ref.borrow()).chars().collect();
+                let label_len = label.len().min(width);
+                let column = (centre * width as f64) as usize;
+                let start = column.saturating_sub(label_len / 2).min(width - label_len);
+                line[start..start + label_len].copy_from_slice(&label[..label_len]);
+            }
+            lines.push(line.into_iter().collect::<String>().trim_end().to_string());
+        }
+        lines.join("\n")
+    }
+
+    /// Prints `Tree::terminal_string` using the width of the current terminal.
+    pub fn print_terminal(&self) {
+        let width = termsize::get()
+            .map(|size| size.cols as usize)
+            .unwrap_or(DEFAULT_TERMINAL_WIDTH);
+        println!("{}", self.terminal_string(width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Tree, TreeNode};
+
+    #[test]
+    fn terminal_string() {
+        // Test tree:
+        //                 1
+        //                / \
+        //               2   3
+        //              / \
+        //             4   5
+        //
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node1 = TreeNode::new_rc(1, Some(node2), Some(node3));
+
+        let tree = Tree::new(node1);
+        assert_eq!(tree.terminal_string(12), "      1\n   2     3\n 4  5");
+    }
+}
@@ -0,0 +1,182 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// A node of a `ThreadedTree`. Children are indexes into the tree's node list.
+#[derive(Debug, Clone, PartialEq)]
+struct ThreadedNode<T> {
+    value: T,
+    id: Uuid,
+    left: Option<usize>,
+    /// The right child, or the in-order successor when `right_is_thread` is set.
+    right: Option<usize>,
+    right_is_thread: bool,
+}
+
+/// A read-only, right-threaded copy of a tree, built by `Tree::to_threaded`.
+///
+/// Every node without a right child points to its in-order successor instead, so the in-order
+/// and pre-order iterators walk the tree with constant state and no stack. Nodes live in one
+/// list and refer to each other by index, which keeps the threads from forming reference cycles.
+///
+/// This does not save memory: the tree is a full copy holding every value, id and link of the
+/// original, and building it takes O(n) extra memory on top of the original tree. It pays off
+/// when the same tree is traversed many times. For a single traversal without extra memory, use
+/// `Tree::morris_in_order` or `Tree::morris_pre_order`, which thread the original nodes
+/// temporarily instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadedTree<T> {
+    /// The nodes in pre-order, so the root is first and every child comes after its parent.
+    nodes: Vec<ThreadedNode<T>>,
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// A threaded copy of the tree, whose traversals need no stack. The copy itself takes O(n)
+    /// memory, see `ThreadedTree`.
+    pub fn to_threaded(&self) -> ThreadedTree<T> {
+        let order = self.pre_order_with_parents();
+        let position: HashMap<*const RefCell<TreeNode<T>>, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(position, (node_ref, _))| (Rc::as_ptr(node_ref), position))
+            .collect();
+        let index_of = |child: &Option<TreeNodeRef<T>>| {
+            child.as_ref().map(|child| position[&Rc::as_ptr(child)])
+        };
+        let mut nodes: Vec<ThreadedNode<T>> = order
+            .iter()
+            .map(|(node_ref, _)| {
+                let node = node_ref.borrow();
+                ThreadedNode {
+                    value: node.value,
+                    id: node.id,
+                    left: index_of(&node.left),
+                    right: index_of(&node.right),
+                    right_is_thread: false,
+                }
+            })
+            .collect();
+
+        let in_order: Vec<usize> = self
+            .in_order_nodes()
+            .iter()
+            .map(|node_ref| position[&Rc::as_ptr(node_ref)])
+            .collect();
+        for pair in in_order.windows(2) {
+            let node = &mut nodes[pair[0]];
+            if node.right.is_none() {
+                node.right = Some(pair[1]);
+                node.right_is_thread = true;
+            }
+        }
+        ThreadedTree { nodes }
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> ThreadedTree<T> {
+    /// The values of the tree in in-order, with their node ids.
+    pub fn in_order(&self) -> impl Iterator<Item = (Uuid, T)> + '_ {
+        let mut next = Some(self.leftmost(0));
+        std::iter::from_fn(move || {
+            let current = next?;
+            let node = &self.nodes[current];
+            next = match node.right {
+                Some(right) if node.right_is_thread => Some(right),
+                Some(right) => Some(self.leftmost(right)),
+                None => None,
+            };
+            Some((node.id, node.value))
+        })
+    }
+
+    /// The values of the tree in pre-order, with their node ids.
+    pub fn pre_order(&self) -> impl Iterator<Item = (Uuid, T)> + '_ {
+        let mut next = Some(0);
+        std::iter::from_fn(move || {
+            let current = next?;
+            let node = &self.nodes[current];
+            next = match node.left {
+                Some(left) => Some(left),
+                None => {
+                    // Follow threads up to the first ancestor with a real right child.
+                    let mut at = current;
+                    while self.nodes[at].right_is_thread {
+                        at = self.nodes[at].right.expect("threads have a target");
+                    }
+                    self.nodes[at].right
+                }
+            };
+            Some((node.id, node.value))
+        })
+    }
+
+    /// Converts back to a regular tree. The nodes keep their ids.
+    pub fn to_tree(&self) -> Tree<T> {
+        let mut built: Vec<Option<TreeNodeRef<T>>> = vec![None; self.nodes.len()];
+        for (position, node) in self.nodes.iter().enumerate().rev() {
+            let mut take = |child: Option<usize>| child.and_then(|child| built[child].take());
+            let left = take(node.left);
+            let right = if node.right_is_thread {
+                None
+            } else {
+                take(node.right)
+            };
+            let copy = TreeNode::new_with_id(node.value, left, right, node.id);
+            built[position] = Some(Rc::new(RefCell::new(copy)));
+        }
+        Tree::new(built[0].take().expect("root is built"))
+    }
+
+    fn leftmost(&self, mut at: usize) -> usize {
+        while let Some(left) = self.nodes[at].left {
+            at = left;
+        }
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //                /     \
+    //               7       8
+    //
+    fn test_tree() -> Tree<i32> {
+        let node7 = TreeNode::new_rc(7, None, None);
+        let node8 = TreeNode::new_rc(8, None, None);
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, Some(node7), None);
+        let node6 = TreeNode::new_rc(6, None, Some(node8));
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node3 = TreeNode::new_rc(3, None, Some(node6));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    #[test]
+    fn threaded_orders() {
+        let tree = test_tree();
+        let threaded = tree.to_threaded();
+        let in_order: Vec<i32> = threaded.in_order().map(|(_, value)| value).collect();
+        assert_eq!(in_order, vec![4, 2, 7, 5, 1, 3, 6, 8]);
+        let pre_order: Vec<i32> = threaded.pre_order().map(|(_, value)| value).collect();
+        assert_eq!(pre_order, vec![1, 2, 4, 5, 7, 3, 6, 8]);
+        assert_eq!(
+            threaded.pre_order().next().unwrap().0,
+            tree.root.borrow().get_id()
+        );
+
+        assert!(threaded.to_tree().is_identical(&tree));
+    }
+}
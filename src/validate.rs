@@ -0,0 +1,250 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
+
+use uuid::Uuid;
+
+use crate::{Tree, TreeNodeRef};
+
+/// Problems found by `Tree::validate`. Nodes are reported by id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Nodes that are their own descendant. Each entry is the `(parent, child)` edge that leads
+    /// back up to an ancestor, so `child` is where the cycle closes.
+    pub cycles: Vec<(Uuid, Uuid)>,
+    /// Nodes reachable through more than one parent, making the structure a DAG rather than a
+    /// tree. Each node is reported once.
+    pub shared: Vec<Uuid>,
+    /// Ids carried by more than one distinct node.
+    pub duplicate_ids: Vec<Uuid>,
+    /// Entries of the id index that point at a node that has been dropped, is no longer in the
+    /// tree, or has a different parent than recorded.
+    pub dangling: Vec<Uuid>,
+    /// Nodes of the tree that are missing from the id index.
+    pub unindexed: Vec<Uuid>,
+}
+
+impl ValidationReport {
+    /// Whether the nodes form a proper tree, with no cycles and no shared nodes.
+    pub fn is_tree(&self) -> bool {
+        self.cycles.is_empty() && self.shared.is_empty()
+    }
+
+    /// Whether no problems at all were found.
+    pub fn is_valid(&self) -> bool {
+        self.is_tree()
+            && self.duplicate_ids.is_empty()
+            && self.dangling.is_empty()
+            && self.unindexed.is_empty()
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Checks that the nodes reachable from the root form a proper tree and that the id index, if
+    /// any, matches it.
+    ///
+    /// Unlike the other traversals this one is safe to run on a broken tree: every node is
+    /// entered at most once, so cycles and shared nodes are reported instead of looping forever or
+    /// being visited twice.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        let mut seen: HashSet<*const _> = HashSet::new();
+        let mut on_path: HashSet<*const _> = HashSet::new();
+        let mut shared: HashSet<*const _> = HashSet::new();
+        let mut ids: HashMap<Uuid, *const _> = HashMap::new();
+        let mut parents: HashMap<Uuid, Option<Uuid>> = HashMap::new();
+
+        // Depth-first walk, the flag marks whether the node is being entered or left. A node is
+        // marked as seen when it is entered, so reaching a node that has been entered but not left
+        // closes a cycle, while reaching one that has been left means it is shared.
+        let mut stack: Vec<(TreeNodeRef<T>, Option<Uuid>, bool)> =
+            vec![(self.root.clone(), None, true)];
+        while let Some((current, parent, entering)) = stack.pop() {
+            let pointer = Rc::as_ptr(&current);
+            if !entering {
+                on_path.remove(&pointer);
+                continue;
+            }
+            let node = current.borrow();
+            // Pushed twice and entered through the other parent in the meantime.
+            if !seen.insert(pointer) {
+                if shared.insert(pointer) {
+                    report.shared.push(node.id);
+                }
+                continue;
+            }
+            on_path.insert(pointer);
+            stack.push((current.clone(), parent, false));
+
+            // Nodes are entered at most once, so a known id means a second node carries it.
+            match ids.entry(node.id) {
+                Entry::Occupied(_) => {
+                    if !report.duplicate_ids.contains(&node.id) {
+                        report.duplicate_ids.push(node.id);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(pointer);
+                    parents.insert(node.id, parent);
+                }
+            }
+
+            for child in [&node.right, &node.left].into_iter().flatten() {
+                let child_pointer = Rc::as_ptr(child);
+                if on_path.contains(&child_pointer) {
+                    report.cycles.push((node.id, child.borrow().id));
+                } else if seen.contains(&child_pointer) {
+                    if shared.insert(child_pointer) {
+                        report.shared.push(child.borrow().id);
+                    }
+                } else {
+                    stack.push((child.clone(), Some(node.id), true));
+                }
+            }
+        }
+
+        if let Some(index) = &self.index {
+            for (id, entry) in index {
+                let matches = entry.node.upgrade().is_some_and(|node_ref| {
+                    ids.get(id) == Some(&Rc::as_ptr(&node_ref))
+                        && parents.get(id) == Some(&entry.parent)
+                });
+                if !matches {
+                    report.dangling.push(*id);
+                }
+            }
+            report.unindexed = ids
+                .keys()
+                .filter(|id| !index.contains_key(id))
+                .copied()
+                .collect();
+        }
+
+        report
+    }
+
+    /// Panics in debug builds if the tree has a cycle or shared nodes. Called at the start of the
+    /// mutation methods, whose traversals would otherwise loop forever on a cyclic tree. This makes
+    /// every edit O(n) in debug builds, the `no-edit-validation` feature turns it off.
+    pub(crate) fn debug_validate(&self) {
+        #[cfg(all(debug_assertions, not(feature = "no-edit-validation")))]
+        {
+            let report = self.validate();
+            assert!(
+                report.is_tree(),
+                "Attempted to modify a tree with cycles or shared nodes: {:?}",
+                report
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeNode;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \
+    //             4   5
+    //
+    fn test_tree() -> (Tree<i32>, Vec<TreeNodeRef<i32>>) {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node3 = TreeNode::new_rc(3, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4.clone()), Some(node5.clone()));
+        let node1 = TreeNode::new_rc(1, Some(node2.clone()), Some(node3.clone()));
+        (
+            Tree::new_indexed(node1.clone()),
+            vec![node1, node2, node3, node4, node5],
+        )
+    }
+
+    #[test]
+    fn valid_tree() {
+        let (tree, _) = test_tree();
+        let report = tree.validate();
+        assert!(report.is_valid());
+        assert_eq!(report, ValidationReport::default());
+    }
+
+    #[test]
+    fn cycle() {
+        let (tree, nodes) = test_tree();
+        nodes[4].borrow_mut().left = Some(nodes[1].clone());
+        let report = tree.validate();
+        assert!(!report.is_tree());
+        assert_eq!(
+            report.cycles,
+            vec![(nodes[4].borrow().get_id(), nodes[1].borrow().get_id())]
+        );
+
+        // Break the cycle again so the nodes can be dropped.
+        nodes[4].borrow_mut().left = None;
+    }
+
+    #[test]
+    fn cycle_through_sibling() {
+        let (tree, nodes) = test_tree();
+        // 2 -> 4 -> 3 -> 2, where 3 is not an ancestor of 4.
+        nodes[3].borrow_mut().left = Some(nodes[2].clone());
+        nodes[2].borrow_mut().left = Some(nodes[1].clone());
+        let report = tree.validate();
+        assert_eq!(
+            report.cycles,
+            vec![(nodes[2].borrow().get_id(), nodes[1].borrow().get_id())]
+        );
+        assert_eq!(report.shared, vec![nodes[2].borrow().get_id()]);
+
+        nodes[2].borrow_mut().left = None;
+    }
+
+    #[test]
+    fn shared_node() {
+        let (tree, nodes) = test_tree();
+        nodes[2].borrow_mut().right = Some(nodes[3].clone());
+        let report = tree.validate();
+        assert!(!report.is_tree());
+        assert_eq!(report.shared, vec![nodes[3].borrow().get_id()]);
+        assert!(report.cycles.is_empty());
+    }
+
+    #[test]
+    fn duplicate_ids_and_dangling_index() {
+        let (tree, nodes) = test_tree();
+        let copy = TreeNode::new_rc(9, None, None);
+        copy.borrow_mut().id = nodes[3].borrow().get_id();
+        nodes[2].borrow_mut().left = Some(copy);
+        nodes[1].borrow_mut().right = None;
+
+        let report = tree.validate();
+        assert!(report.is_tree());
+        assert_eq!(report.duplicate_ids, vec![nodes[3].borrow().get_id()]);
+        assert_eq!(report.dangling, vec![nodes[4].borrow().get_id()]);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to modify a tree with cycles or shared nodes")]
+    #[cfg(all(debug_assertions, not(feature = "no-edit-validation")))]
+    fn mutation_on_cyclic_tree() {
+        let (mut tree, nodes) = test_tree();
+        nodes[4].borrow_mut().left = Some(nodes[0].clone());
+        let id = nodes[2].borrow().get_id();
+        let _ = tree.insert_left(id, 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to modify a tree with cycles or shared nodes")]
+    #[cfg(all(debug_assertions, not(feature = "no-edit-validation")))]
+    fn cursor_edit_on_shared_tree() {
+        let (mut tree, nodes) = test_tree();
+        nodes[2].borrow_mut().left = Some(nodes[3].clone());
+        let _ = tree.cursor().insert_right(6);
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt::{Debug, Display};
+
+use uuid::Uuid;
+
+use crate::edit::Side;
+use crate::{Tree, TreeNode, TreeNodeRef};
+
+/// What a `TreeVisitor` wants the walk to do after one of its callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitFlow {
+    /// Carry on with the walk.
+    Continue,
+    /// From `enter_node`, skip the node's children. From an edge callback, skip that child.
+    /// Anywhere else the same as `Continue`.
+    SkipChildren,
+    /// End the walk immediately, without calling any further callbacks.
+    Stop,
+}
+
+/// Callbacks for `Tree::walk`, which visits the tree depth first, left before right.
+///
+/// For every node the walk calls `enter_node`, then for each child the matching edge callback
+/// followed by the child's whole subtree, and finally `exit_node`. `depth` counts from 0 at the
+/// node the walk started from. Every callback has a default that does nothing, so visitors only
+/// implement what they need.
+pub trait TreeVisitor<T: Sized + Copy> {
+    fn enter_node(&mut self, _node: &TreeNode<T>, _depth: usize) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    /// Called after the node's children, also when they were skipped.
+    fn exit_node(&mut self, _node: &TreeNode<T>, _depth: usize) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn visit_left_edge(&mut self, _parent: &TreeNode<T>, _child: &TreeNode<T>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn visit_right_edge(&mut self, _parent: &TreeNode<T>, _child: &TreeNode<T>) -> VisitFlow {
+        VisitFlow::Continue
+    }
+}
+
+/// One pending step of a walk. Nodes below the starting node are held by `Rc`, the starting node
+/// itself is only borrowed, so it is written as `None`.
+enum Step<T: Sized + Copy> {
+    Edge(Option<TreeNodeRef<T>>, TreeNodeRef<T>, Side, usize),
+    Enter(TreeNodeRef<T>, usize),
+    Exit(Option<TreeNodeRef<T>>, usize),
+}
+
+impl<T: Sized + Copy> TreeNode<T> {
+    /// Walks the subtree below this node with `visitor`, see `TreeVisitor`. Returns
+    /// `VisitFlow::Stop` if the visitor ended the walk early and `VisitFlow::Continue` otherwise.
+    ///
+    /// The walk keeps its own stack, so it handles trees of any depth.
+    pub fn walk(&self, visitor: &mut impl TreeVisitor<T>) -> VisitFlow {
+        let mut stack: Vec<Step<T>> = Vec::new();
+        let push_node = |stack: &mut Vec<Step<T>>,
+                         node: &TreeNode<T>,
+                         node_ref: Option<TreeNodeRef<T>>,
+                         depth: usize,
+                         flow: VisitFlow| {
+            stack.push(Step::Exit(node_ref.clone(), depth));
+            if flow == VisitFlow::SkipChildren {
+                return;
+            }
+            for side in [Side::Right, Side::Left] {
+                if let Some(child) = node.child(side) {
+                    stack.push(Step::Edge(node_ref.clone(), child.clone(), side, depth));
+                }
+            }
+        };
+
+        match visitor.enter_node(self, 0) {
+            VisitFlow::Stop => return VisitFlow::Stop,
+            flow => push_node(&mut stack, self, None, 0, flow),
+        }
+        while let Some(step) = stack.pop() {
+            let flow = match step {
+                Step::Edge(parent, child, side, depth) => {
+                    let parent = parent.as_ref().map(|parent| parent.borrow());
+                    let parent = parent.as_deref().unwrap_or(self);
+                    let flow = match side {
+                        Side::Left => visitor.visit_left_edge(parent, &child.borrow()),
+                        Side::Right => visitor.visit_right_edge(parent, &child.borrow()),
+                    };
+                    if flow == VisitFlow::Continue {
+                        stack.push(Step::Enter(child, depth + 1));
+                    }
+                    flow
+                }
+                Step::Enter(node_ref, depth) => {
+                    let node = node_ref.borrow();
+                    let flow = visitor.enter_node(&node, depth);
+                    if flow != VisitFlow::Stop {
+                        push_node(&mut stack, &node, Some(node_ref.clone()), depth, flow);
+                    }
+                    flow
+                }
+                Step::Exit(node_ref, depth) => {
+                    let node = node_ref.as_ref().map(|node_ref| node_ref.borrow());
+                    visitor.exit_node(node.as_deref().unwrap_or(self), depth)
+                }
+            };
+            if flow == VisitFlow::Stop {
+                return VisitFlow::Stop;
+            }
+        }
+        VisitFlow::Continue
+    }
+}
+
+impl<T: Sized + Copy + Debug + Display> Tree<T> {
+    /// Walks the whole tree with `visitor`, see `TreeVisitor` and `TreeNode::walk`.
+    pub fn walk(&self, visitor: &mut impl TreeVisitor<T>) -> VisitFlow {
+        self.root.borrow().walk(visitor)
+    }
+}
+
+/// Builds the nested Typst array behind `TreeNode::format_typst_with`.
+pub(crate) struct TypstVisitor<'a, T: Sized + Copy> {
+    pub(crate) label: &'a dyn Fn(&TreeNode<T>) -> String,
+    pub(crate) out: String,
+    /// For every node on the current path below the start, whether it is a left child.
+    left_child: Vec<bool>,
+}
+
+impl<'a, T: Sized + Copy> TypstVisitor<'a, T> {
+    pub(crate) fn new(label: &'a dyn Fn(&TreeNode<T>) -> String) -> Self {
+        TypstVisitor {
+            label,
+            out: String::new(),
+            left_child: Vec::new(),
+        }
+    }
+}
+
+impl<T: Sized + Copy> TreeVisitor<T> for TypstVisitor<'_, T> {
+    fn enter_node(&mut self, node: &TreeNode<T>, _depth: usize) -> VisitFlow {
+        if node.left.is_none() && node.right.is_none() {
+            self.out.push_str(&(self.label)(node));
+        } else {
+            self.out.push('(');
+            self.out.push_str(&(self.label)(node));
+            self.out.push_str(", ");
+        }
+        VisitFlow::Continue
+    }
+
+    fn exit_node(&mut self, node: &TreeNode<T>, depth: usize) -> VisitFlow {
+        if node.left.is_some() || node.right.is_some() {
+            self.out.push(')');
+        }
+        if depth > 0 && self.left_child.pop() == Some(true) {
+            self.out.push_str(", ");
+        }
+        VisitFlow::Continue
+    }
+
+    fn visit_left_edge(&mut self, _parent: &TreeNode<T>, _child: &TreeNode<T>) -> VisitFlow {
+        self.left_child.push(true);
+        VisitFlow::Continue
+    }
+
+    fn visit_right_edge(&mut self, _parent: &TreeNode<T>, _child: &TreeNode<T>) -> VisitFlow {
+        self.left_child.push(false);
+        VisitFlow::Continue
+    }
+}
+
+/// Finds the depth of the node with a given id, stopping as soon as it is found.
+pub(crate) struct DepthVisitor {
+    pub(crate) id: Uuid,
+    pub(crate) depth: Option<usize>,
+}
+
+impl<T: Sized + Copy> TreeVisitor<T> for DepthVisitor {
+    fn enter_node(&mut self, node: &TreeNode<T>, depth: usize) -> VisitFlow {
+        if node.id == self.id {
+            self.depth = Some(depth);
+            return VisitFlow::Stop;
+        }
+        VisitFlow::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test tree:
+    //                 1
+    //                / \
+    //               2   3
+    //              / \   \
+    //             4   5   6
+    //
+    fn test_tree() -> Tree<i32> {
+        let node4 = TreeNode::new_rc(4, None, None);
+        let node5 = TreeNode::new_rc(5, None, None);
+        let node6 = TreeNode::new_rc(6, None, None);
+        let node2 = TreeNode::new_rc(2, Some(node4), Some(node5));
+        let node3 = TreeNode::new_rc(3, None, Some(node6));
+        Tree::new(TreeNode::new_rc(1, Some(node2), Some(node3)))
+    }
+
+    /// Records every callback, skipping below `skip` and stopping on entering `stop`.
+    struct Recorder {
+        events: Vec<String>,
+        skip: i32,
+        stop: i32,
+    }
+
+    impl TreeVisitor<i32> for Recorder {
+        fn enter_node(&mut self, node: &TreeNode<i32>, depth: usize) -> VisitFlow {
+            self.events
+                .push(format!("enter {} at {}", node.value, depth));
+            if node.value == self.stop {
+                VisitFlow::Stop
+            } else if node.value == self.skip {
+                VisitFlow::SkipChildren
+            } else {
+                VisitFlow::Continue
+            }
+        }
+
+        fn exit_node(&mut self, node: &TreeNode<i32>, _depth: usize) -> VisitFlow {
+            self.events.push(format!("exit {}", node.value));
+            VisitFlow::Continue
+        }
+
+        fn visit_left_edge(&mut self, parent: &TreeNode<i32>, child: &TreeNode<i32>) -> VisitFlow {
+            self.events
+                .push(format!("left {}-{}", parent.value, child.value));
+            VisitFlow::Continue
+        }
+
+        fn visit_right_edge(&mut self, parent: &TreeNode<i32>, child: &TreeNode<i32>) -> VisitFlow {
+            self.events
+                .push(format!("right {}-{}", parent.value, child.value));
+            VisitFlow::Continue
+        }
+    }
+
+    #[test]
+    fn walk_order() {
+        let tree = test_tree();
+        let mut recorder = Recorder {
+            events: Vec::new(),
+            skip: 2,
+            stop: 0,
+        };
+        assert_eq!(tree.walk(&mut recorder), VisitFlow::Continue);
+        assert_eq!(
+            recorder.events,
+            vec![
+                "enter 1 at 0",
+                "left 1-2",
+                "enter 2 at 1",
+                "exit 2",
+                "right 1-3",
+                "enter 3 at 1",
+                "right 3-6",
+                "enter 6 at 2",
+                "exit 6",
+                "exit 3",
+                "exit 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn early_exit() {
+        let tree = test_tree();
+        let mut recorder = Recorder {
+            events: Vec::new(),
+            skip: 0,
+            stop: 5,
+        };
+        assert_eq!(tree.walk(&mut recorder), VisitFlow::Stop);
+        assert_eq!(recorder.events.last().unwrap(), "enter 5 at 2");
+        assert_eq!(recorder.events.len(), 8);
+    }
+}
@@ -0,0 +1,253 @@
+use binary_tree_ds::{Side, Tree, TreeNode, TreeNodeProperties, TreeVisitor, VisitFlow};
+
+/// A degenerate tree where every node only has a left child: `length - 1` at the root down to 0.
+fn chain(length: usize) -> Tree<usize> {
+    let mut node = TreeNode::new_rc(0, None, None);
+    for value in 1..length {
+        node = TreeNode::new_rc(value, Some(node), None);
+    }
+    Tree::new(node)
+}
+
+/// Like `chain`, but every node only has a right child.
+fn right_chain(length: usize) -> Tree<usize> {
+    let mut node = TreeNode::new_rc(0, None, None);
+    for value in 1..length {
+        node = TreeNode::new_rc(value, None, Some(node));
+    }
+    Tree::new(node)
+}
+
+/// Counts the nodes a walk enters and the deepest level it reaches.
+#[derive(Default)]
+struct DepthCounter {
+    entered: usize,
+    max_depth: usize,
+}
+
+impl TreeVisitor<usize> for DepthCounter {
+    fn enter_node(&mut self, _node: &TreeNode<usize>, depth: usize) -> VisitFlow {
+        self.entered += 1;
+        self.max_depth = self.max_depth.max(depth);
+        VisitFlow::Continue
+    }
+}
+
+/// Runs every traversal, query, export and edit on a chain of `length` nodes, deep enough that any
+/// of them recursing once per level would overflow the stack.
+fn exercise_chain(length: usize) {
+    let tree = chain(length);
+    let leaf = tree.root.borrow().pre_order_vec().pop().unwrap();
+    let leaf_id = leaf.borrow().get_id();
+    let root_id = tree.root.borrow().get_id();
+
+    assert_eq!(tree.height(), length - 1);
+    assert_eq!(tree.max_depth(), length as isize - 1);
+    assert_eq!(
+        TreeNode::depth(&tree.root.borrow(), &leaf_id),
+        length as isize - 1
+    );
+    assert_eq!(tree.depth_of(&leaf), Some(length - 1));
+    assert!(tree.get_by_id(leaf_id).is_some());
+    assert!(tree.root.get_by_id(leaf_id).is_some());
+    assert_eq!(tree.get_parent(&leaf).borrow().value, 1);
+
+    let typst = tree.typst_string();
+    assert!(typst.starts_with(&format!("([{}], ([{}], ", length - 1, length - 2)));
+    assert!(typst.contains("([2], ([1], [0], ), ), "));
+    assert_eq!(typst.matches(')').count(), length - 1);
+    assert!(format!("{:?}", tree.root.borrow()).contains("TreeNode"));
+
+    let copy = tree.deep_clone();
+    assert!(tree == copy);
+    assert!(tree.is_identical(&copy));
+    assert_eq!(tree.structural_hash(), copy.structural_hash());
+    copy.root.borrow_mut().value = length;
+    assert!(tree != copy);
+    drop(copy);
+
+    assert!(tree.validate().is_valid());
+    tree.annotate();
+    assert_eq!(tree.annotation(root_id).unwrap().size, length);
+    assert_eq!(tree.size(), length);
+    assert_eq!(
+        tree.fold_in_order(0, |sum, value| sum + value),
+        length * (length - 1) / 2
+    );
+    assert_eq!(tree.map(|value| value % 2).height(), length - 1);
+
+    let mut count = 0;
+    tree.morris_in_order(|_| count += 1);
+    assert_eq!(count, length);
+    assert_eq!(tree.to_threaded().pre_order().count(), length);
+
+    assert_eq!(tree.diameter().0, length - 1);
+    assert_eq!(
+        tree.lowest_common_ancestor(root_id, leaf_id)
+            .unwrap()
+            .borrow()
+            .get_id(),
+        root_id
+    );
+
+    assert_eq!(tree.max_width_upper(), None);
+    assert_eq!(tree.level_widths(), vec![1; length]);
+    assert_eq!(tree.max_width(), 1);
+    assert_eq!(tree.max_width_with_gaps(), 1);
+    let paths = tree.root_to_leaf_paths();
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].len(), length);
+    drop(paths);
+    let (sum, path) = tree.max_path_sum();
+    assert_eq!(sum, length * (length - 1) / 2);
+    // The leaf holds 0, which adds nothing to the sum.
+    assert_eq!(path.len(), length - 1);
+    drop(path);
+
+    let path = tree.path_between(leaf_id, root_id).unwrap();
+    assert_eq!(path.len(), length);
+    assert_eq!(path[0].borrow().value, 0);
+    assert_eq!(path[length - 1].borrow().get_id(), root_id);
+    drop(path);
+
+    let mut walker = DepthCounter::default();
+    assert_eq!(tree.walk(&mut walker), VisitFlow::Continue);
+    assert_eq!(walker.entered, length);
+    assert_eq!(walker.max_depth, length - 1);
+
+    let doubled = tree.zip_with(&tree, |a, b| a + b).unwrap();
+    assert_eq!(
+        doubled.fold_in_order(0, |sum, value| sum + value),
+        length * (length - 1)
+    );
+    drop(doubled);
+
+    let terminal = tree.terminal_string(4);
+    assert_eq!(terminal.lines().count(), length);
+    drop(terminal);
+
+    let mirrored = right_chain(length);
+    assert!(tree.is_mirror_of(&mirrored));
+    assert!(tree.is_isomorphic_to(&mirrored));
+    assert!(!tree.contains_subtree(&mirrored));
+    // Every subtree of the chain holds different values, so the duplicates are exactly the
+    // matching subtrees of the copy.
+    let copy = tree.deep_clone();
+    let duplicates = Tree::duplicate_subtrees(&[tree.clone(), copy]);
+    assert_eq!(duplicates.len(), length);
+    assert!(duplicates.iter().all(|group| group.len() == 2));
+    drop(duplicates);
+    drop(mirrored);
+
+    let mut edited = tree.deep_clone();
+    edited.insert_right(leaf_id, length).unwrap();
+    edited.root.borrow_mut().value = length + 1;
+    let edits = tree.diff(&edited);
+    assert_eq!(edits.len(), 2);
+    let mut patched = tree.deep_clone();
+    patched.apply_patch(&edits).unwrap();
+    assert!(patched.is_identical(&edited));
+    drop(edited);
+
+    let pruned = patched.prune(|value| *value == length / 2).unwrap();
+    assert_eq!(pruned.len(), 1);
+    // The pruned subtree takes the leaf added by the patch with it.
+    assert_eq!(pruned[0].size(), length / 2 + 2);
+    assert_eq!(patched.size(), length / 2 - 1);
+    drop(pruned);
+
+    let mut cursor = patched.cursor();
+    while cursor.has_child(Side::Left) {
+        cursor.move_left().unwrap();
+    }
+    assert_eq!(cursor.depth(), length / 2 - 2);
+    assert_eq!(cursor.value(), length / 2 + 1);
+    cursor.insert_right(0).unwrap();
+    assert_eq!(cursor.move_right().unwrap().depth(), length / 2 - 1);
+    assert_eq!(cursor.move_root().value(), length + 1);
+    assert_eq!(patched.size(), length / 2);
+    drop(patched);
+
+    drop(leaf);
+    drop(tree);
+}
+
+#[test]
+fn deep_chain() {
+    exercise_chain(100_000);
+}
+
+#[test]
+#[ignore = "takes over a minute in debug builds, run with `cargo test -- --ignored`"]
+fn million_node_chain() {
+    exercise_chain(1_000_000);
+}
+
+/// The sparse table of `LcaIndex` takes O(n log n) memory, so this uses a shorter chain.
+#[test]
+fn lca_index_on_deep_chain() {
+    let tree = chain(100_000);
+    let leaf = tree.root.borrow().pre_order_vec().pop().unwrap();
+    let leaf_id = leaf.borrow().get_id();
+    let root_id = tree.root.borrow().get_id();
+    let index = tree.lca_index();
+    assert_eq!(index.distance(root_id, leaf_id), Some(99_999));
+    assert_eq!(
+        index.lca(root_id, leaf_id).unwrap().borrow().get_id(),
+        root_id
+    );
+}
+
+/// Runs `f` on a thread with a small stack, where a recursive drop of a deep tree would overflow.
+fn on_small_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn drop_deep_shapes() {
+    on_small_stack(|| {
+        // Left chain, right chain and a zig-zag that alternates sides on the way down.
+        for pick in [|_| true, |_| false, |value: usize| value.is_multiple_of(2)] {
+            let mut node = TreeNode::new_rc(0, None, None);
+            for value in 1..100_000 {
+                node = if pick(value) {
+                    TreeNode::new_rc(value, Some(node), None)
+                } else {
+                    TreeNode::new_rc(value, None, Some(node))
+                };
+            }
+            drop(Tree::new(node));
+        }
+    });
+}
+
+#[test]
+fn drop_with_outside_handles() {
+    on_small_stack(|| {
+        let mut node = TreeNode::new_rc(0, None, None);
+        let mut middle = None;
+        for value in 1..100_000 {
+            node = TreeNode::new_rc(value, Some(node), None);
+            if value == 50_000 {
+                middle = Some(node.clone());
+            }
+        }
+        let mut tree = Tree::new_indexed(node);
+
+        // A handle kept by the caller keeps its node and everything below it alive.
+        let middle = middle.unwrap();
+        let id = middle.borrow().left.as_ref().unwrap().borrow().get_id();
+        let detached = tree.remove_subtree(id).unwrap();
+        drop(tree);
+        assert_eq!(middle.borrow().value, 50_000);
+        assert!(middle.borrow().left.is_none());
+        assert_eq!(detached.height(), 49_999);
+        drop(detached);
+        drop(middle);
+    });
+}